@@ -1,34 +1,81 @@
 use core::{
-    board::{
-        board::Board,
-        defs::{Bitboard, START_POS},
-    },
+    board::board::Board,
     movegen::movegen::MoveGen,
+    search::{defs::MAX_DEPTH, search::Search},
 };
+use std::{env, time::Duration};
 
+const BENCH_POSITIONS: [&str; 4] = [
+    "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+    "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+    "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+    "r1bq1rk1/pp2bppp/2n2n2/3p4/3P4/2NBPN2/PP3PPP/R2QK2R w KQ - 0 9",
+];
+
+// Without arguments prints a perft divide, `bench [threads] [movetime ms]` runs fixed-time searches
 pub fn main() {
+    let args: Vec<String> = env::args().collect();
+    let mg = MoveGen;
+
+    if args.get(1).map(String::as_str) == Some("bench") {
+        let threads = args.get(2).and_then(|arg| arg.parse().ok()).unwrap_or(1);
+        let movetime = args.get(3).and_then(|arg| arg.parse().ok()).unwrap_or(1000);
+        bench(&mg, threads, Duration::from_millis(movetime));
+        return;
+    }
+
     let mut board =
         Board::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - ")
             .unwrap();
-    let mg = MoveGen;
 
     print_perft(&mut board, &mg, 4);
 }
 
+fn bench(mg: &MoveGen, threads: usize, movetime: Duration) {
+    let mut search = Search::new(16);
+    search.set_threads(threads);
+
+    let mut total_depth = 0;
+    let mut total_nodes = 0;
+    for fen in BENCH_POSITIONS {
+        let mut board = Board::from_fen(fen).unwrap();
+        search.clear();
+        search.find_best_move_iter(&mut board, mg, MAX_DEPTH, movetime);
+
+        println!(
+            "depth {:>2} nodes {:>10} {}",
+            search.completed_depth,
+            search.total_nodes(),
+            fen
+        );
+        total_depth += search.completed_depth;
+        total_nodes += search.total_nodes();
+    }
+
+    let seconds = movetime.as_secs_f64() * BENCH_POSITIONS.len() as f64;
+    println!(
+        "Threads {} depth {} nodes {} nps {}",
+        threads,
+        total_depth,
+        total_nodes,
+        (total_nodes as f64 / seconds) as u64
+    );
+}
+
 fn print_perft(board: &mut Board, mg: &MoveGen, depth: usize) {
     let moves = mg.gen_legal_moves(board);
     for _move in moves.iter() {
-        board.do_move(&_move);
+        board.do_move(_move);
         println!(
             "{}{} {}",
             square_to_algebraic(_move.from()),
             square_to_algebraic(_move.to()),
-            test_perft_nodes(depth - 1, &mg, board)
+            test_perft_nodes(depth - 1, mg, board)
         );
-        board.undo_move(&_move);
+        board.undo_move(_move);
     }
 
-    println!("Total {}", test_perft_nodes(depth, &mg, board));
+    println!("Total {}", test_perft_nodes(depth, mg, board));
 }
 
 fn test_perft_nodes(depth: usize, mg: &MoveGen, board: &mut Board) -> usize {
@@ -41,13 +88,13 @@ fn test_perft_nodes(depth: usize, mg: &MoveGen, board: &mut Board) -> usize {
     let moves = mg.gen_moves(board);
 
     for _move in moves.iter() {
-        board.do_move(&_move);
+        board.do_move(_move);
         if mg.in_check(board, board.them()) {
-            board.undo_move(&_move);
+            board.undo_move(_move);
             continue;
         }
         count += test_perft_nodes(depth - 1, mg, board);
-        board.undo_move(&_move);
+        board.undo_move(_move);
     }
 
     count
@@ -1,54 +1,71 @@
-use std::collections::{hash_map::Entry, HashMap};
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    fmt,
+};
 
-use crate::movegen::{movegen::bitscan_forward, moves::Move};
+use crate::movegen::{
+    movegen::{bitscan_forward, MoveGen},
+    moves::Move,
+};
 
+use super::cuckoo::{between, Cuckoo};
 use super::defs::{
-    Bitboard, InvalidFenError, Piece, Pieces, Side, Sides, Square, ZobristHash, BB_SQUARES,
-    EMPTY,
+    Bitboard, GameResult, InvalidFenError, Piece, Pieces, Side, Sides, Square, ZobristHash,
+    BB_SQUARES, EMPTY, LIGHT_SQUARES,
 };
 
 pub fn algebraic_to_square(alg: &str) -> usize {
-    let chars: Vec<char> = alg.chars().collect();
-    if chars.len() != 2 {
-        panic!(
-            "Algebraic notation must be exactly 2 characters, e.g. 'a1'. Got: {}",
-            alg
-        );
+    match parse_square(alg) {
+        Some(square) => square,
+        None => panic!("Invalid algebraic square, e.g. 'a1'. Got: {}", alg),
     }
+}
 
-    let file_char = chars[0];
-    let rank_char = chars[1];
-
-    let file = (file_char as u8)
-        .checked_sub(b'a')
-        .expect("Invalid file character");
+// Squares are indexed from a8 (0) to h1 (63), so rank 8 is the first row
+pub fn parse_square(alg: &str) -> Option<Square> {
+    let bytes = alg.as_bytes();
+    if bytes.len() != 2 {
+        return None;
+    }
 
-    let rank = (rank_char as u8)
-        .checked_sub(b'1')
-        .expect("Invalid rank character");
+    let file = bytes[0].checked_sub(b'a')?;
+    let rank = bytes[1].checked_sub(b'1')?;
 
     if file > 7 || rank > 7 {
-        panic!(
-            "File '{}' or rank '{}' out of valid range",
-            file_char, rank_char
-        );
+        return None;
     }
 
-    (rank as usize) * 8 + file as usize
+    Some((7 - rank as usize) * 8 + file as usize)
 }
 
+pub fn square_to_algebraic(square: Square) -> String {
+    let file = (b'a' + (square % 8) as u8) as char;
+    let rank = (b'8' - (square / 8) as u8) as char;
+
+    format!("{}{}", file, rank)
+}
+
+const PIECE_CHARS: [char; 6] = ['p', 'b', 'n', 'r', 'q', 'k'];
+
+#[derive(Clone)]
 pub struct ZobristRandoms {
     rnd_pieces: [[[u64; 64]; 6]; 2],
     rnd_castling: [u64; 4],
     rnd_side: [u64; 2],
     rnd_en_passant: [u64; 65],
 }
+impl Default for ZobristRandoms {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl ZobristRandoms {
     // musl lcg
     fn next_random(rand: &mut u64) {
         *rand = rand.wrapping_mul(6364136223846793005).wrapping_add(1);
     }
-    fn new() -> ZobristRandoms {
+    pub fn new() -> ZobristRandoms {
         let mut rnd_pieces = [[[0u64; 64]; 6]; 2];
         let mut rnd_castling = [0u64; 4];
         let mut rnd_side = [0u64; 2];
@@ -57,28 +74,24 @@ impl ZobristRandoms {
         let mut rand = 1;
 
         // Random pieces
-        for side in 0..2 {
-            for piece in 0..6 {
-                for square in 0..64 {
-                    Self::next_random(&mut rand);
-                    rnd_pieces[side][piece][square] = rand;
-                }
-            }
+        for random in rnd_pieces.iter_mut().flatten().flatten() {
+            Self::next_random(&mut rand);
+            *random = rand;
         }
 
-        for i in 0..4 {
+        for random in rnd_castling.iter_mut() {
             Self::next_random(&mut rand);
-            rnd_castling[i] = rand;
+            *random = rand;
         }
 
-        for i in 0..2 {
+        for random in rnd_side.iter_mut() {
             Self::next_random(&mut rand);
-            rnd_side[i] = rand;
+            *random = rand;
         }
 
-        for i in 0..65 {
+        for random in rnd_en_passant.iter_mut() {
             Self::next_random(&mut rand);
-            rnd_en_passant[i] = rand;
+            *random = rand;
         }
 
         ZobristRandoms {
@@ -88,27 +101,54 @@ impl ZobristRandoms {
             rnd_en_passant,
         }
     }
+
+    pub fn piece(&self, side: Side, piece: Piece, square: Square) -> u64 {
+        self.rnd_pieces[side][piece][square]
+    }
+
+    // Difference between the keys of the same position with either side to move
+    pub fn side_to_move(&self) -> u64 {
+        self.rnd_side[Sides::WHITE] ^ self.rnd_side[Sides::BLACK]
+    }
 }
 
+#[derive(Clone)]
 pub struct History {
     stack: Vec<GameState>,
+    // Key of the position each entry of `stack` was saved in, oldest first
+    keys: Vec<ZobristHash>,
     counts: HashMap<ZobristHash, usize>,
 }
+impl Default for History {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl History {
     pub fn new() -> History {
         History {
             stack: vec![],
+            keys: vec![],
             counts: HashMap::with_capacity(2048),
         }
     }
-    pub fn add_entry(&mut self, game_state: &GameState) {
+    pub fn add_entry(&mut self, game_state: &GameState, key: ZobristHash) {
         self.stack.push(game_state.clone());
+        self.keys.push(key);
     }
 
     pub fn pop_entry(&mut self) -> GameState {
+        self.keys.pop();
         self.stack.pop().expect("Don't pop an empty history!")
     }
 
+    // Key of the position `distance` plies before the current one
+    pub fn key(&self, distance: usize) -> Option<ZobristHash> {
+        let index = self.keys.len().checked_sub(distance)?;
+        self.keys.get(index).copied()
+    }
+
     pub fn increment_hash(&mut self, hash: u64) {
         *self.counts.entry(hash).or_insert(0) += 1;
     }
@@ -135,8 +175,12 @@ pub struct GameState {
     pub active_color: Side,
     pub castling_permissions: u8,
     pub enpassant_piece: Option<Square>,
-    // To not null move twice, when doing nmh
-    pub can_nullmove: bool,
+    // Plies since the last capture or pawn move, for the fifty-move rule
+    pub halfmove_clock: usize,
+    // Starts at 1 and goes up after every move of black, as in FEN
+    pub fullmove_number: usize,
+    // Plies since the last null move, repetitions can't reach across one
+    pub plies_from_null: usize,
 }
 
 impl GameState {
@@ -158,57 +202,53 @@ impl GameState {
             shift += 1;
         }
 
-        if self.castling_permissions >> shift & 1 == 1 {
-            true
-        } else {
-            false
-        }
+        self.castling_permissions >> shift & 1 == 1
     }
 }
 
+#[derive(Clone)]
 pub struct Board {
     pub bb_pieces: [[Bitboard; 6]; 2],
     pub bb_side: [Bitboard; 3],
     pub game_state: GameState,
     pub history: History,
     pub piece_list: [Option<Piece>; 64],
+    // Starting square of the rook for each side's short and long castle, fixed for the game
+    pub castling_rooks: [[Square; 2]; 2],
     zobrist_randoms: ZobristRandoms,
+    // Zobrist key of the pieces alone, kept up to date by add_piece and remove_piece
+    piece_key: ZobristHash,
 }
 
 impl Board {
     pub fn do_move(&mut self, _move: &Move) {
-        self.history.add_entry(&self.game_state);
-        self.remove_piece(_move.to());
-        self.remove_piece(_move.from());
+        self.history.add_entry(&self.game_state, self.zobrist_hash());
 
-        if _move.is_promotion() {
-            self.add_piece(_move.to(), _move.promotion_piece(), self.us());
+        self.game_state.plies_from_null += 1;
+        if _move.piece() == Pieces::PAWN || _move.capture().is_some() {
+            self.game_state.halfmove_clock = 0;
         } else {
-            self.add_piece(_move.to(), _move.piece(), self.us());
+            self.game_state.halfmove_clock += 1;
         }
 
         if _move.is_castle() {
-            // Remove rook that is one past
-            let long_castle: bool = match _move.to() as isize - _move.from() as isize {
-                -2 => true,
-                2 => false,
-                _ => panic!("Invalid castle, {:?}", _move),
-            };
-
-            let rook_remove_offset = if long_castle { -2 } else { 1 };
-            let rook_add_offset = if long_castle { -1 } else { 1 };
-
-            self.remove_piece((_move.to() as isize + rook_remove_offset) as usize);
-            self.add_piece(
-                (_move.to() as isize - rook_add_offset) as usize,
-                Pieces::ROOK,
-                self.us(),
-            );
+            // Castling is encoded as the king capturing its own rook, which also covers
+            // Chess960 setups where the king or rook doesn't move
+            let (king_to, rook_to) = _move.castle_destinations();
+            self.remove_piece(_move.from());
+            self.remove_piece(_move.to());
+            self.add_piece(king_to, Pieces::KING, self.us());
+            self.add_piece(rook_to, Pieces::ROOK, self.us());
+        } else {
+            self.remove_piece(_move.to());
+            self.remove_piece(_move.from());
 
-            // Disable long and short castle
-            self.game_state.disable_castle(self.us(), true);
-            self.game_state.disable_castle(self.us(), false);
-        };
+            if _move.is_promotion() {
+                self.add_piece(_move.to(), _move.promotion_piece(), self.us());
+            } else {
+                self.add_piece(_move.to(), _move.piece(), self.us());
+            }
+        }
 
         self.game_state.enpassant_piece = None;
         if _move.is_double_hop() && _move.piece() == Pieces::PAWN {
@@ -228,44 +268,162 @@ impl Board {
         }
 
         // Remove castling permission if rook is moved or captured
-        match _move.to() {
-            63 => self.game_state.disable_castle(Sides::WHITE, false),
-            56 => self.game_state.disable_castle(Sides::WHITE, true),
-            7 => self.game_state.disable_castle(Sides::BLACK, false),
-            0 => self.game_state.disable_castle(Sides::BLACK, true),
-            _ => (),
-        }
-
-        match _move.from() {
-            63 => self.game_state.disable_castle(Sides::WHITE, false),
-            56 => self.game_state.disable_castle(Sides::WHITE, true),
-            7 => self.game_state.disable_castle(Sides::BLACK, false),
-            0 => self.game_state.disable_castle(Sides::BLACK, true),
-            _ => (),
+        for side in 0..2 {
+            for long_castle in [false, true] {
+                let rook = self.castling_rooks[side][long_castle as usize];
+                if _move.from() == rook || _move.to() == rook {
+                    self.game_state.disable_castle(side, long_castle);
+                }
+            }
         }
 
-        // Remove castling if move is king
+        // Remove castling if move is king, castling included
         if _move.piece() == Pieces::KING {
             self.game_state.disable_castle(self.us(), true);
             self.game_state.disable_castle(self.us(), false);
         }
 
+        if self.us() == Sides::BLACK {
+            self.game_state.fullmove_number += 1;
+        }
         self.game_state.active_color = self.them();
         self.history.increment_hash(self.zobrist_hash());
     }
 
-    pub fn zobrist_hash(&self) -> ZobristHash {
-        let mut zobrist_hash: ZobristHash = 0;
-        for side in 0..2 {
-            for piece in 0..6 {
-                let mut current = self.bb_pieces[side][piece];
-                while let Some(square) = bitscan_forward(current) {
-                    current &= current - 1;
-                    zobrist_hash ^= self.zobrist_randoms.rnd_pieces[side][piece][square];
-                }
+    // Passes the turn, for null move pruning. The position is added to the history like any
+    // other, so it takes part in repetitions and undoes the same way.
+    pub fn do_null_move(&mut self) {
+        self.history.add_entry(&self.game_state, self.zobrist_hash());
+        self.game_state.enpassant_piece = None;
+        self.game_state.halfmove_clock += 1;
+        self.game_state.plies_from_null = 0;
+        self.game_state.active_color = self.them();
+        self.history.increment_hash(self.zobrist_hash());
+    }
+
+    pub fn undo_null_move(&mut self) {
+        self.history.decrement_hash(self.zobrist_hash());
+        self.game_state = self.history.pop_entry();
+    }
+
+    // Whether the position occurred before, with `ply` the distance from the search root. Once
+    // inside the search tree is enough to call it a draw, as the side that could avoid it would
+    // have. Before the root it has to be the third time, as the rules say.
+    pub fn is_repetition(&self, ply: usize) -> bool {
+        let hash = self.zobrist_hash();
+        // Nothing before the last irreversible move or null move can be the same position
+        let limit = self.game_state.halfmove_clock.min(self.game_state.plies_from_null);
+
+        let mut count = 0;
+        for distance in (4..=limit).step_by(2) {
+            if self.history.key(distance) != Some(hash) {
+                continue;
+            }
+            if distance < ply {
+                return true;
+            }
+            count += 1;
+            if count == 2 {
+                return true;
+            }
+        }
+        false
+    }
+
+    // Whether a single reversible move gets back to a position from earlier in the line, so the
+    // side to move can make sure of a draw. Same rules as is_repetition for before the root, where
+    // the move has to be ours and the position there already repeated.
+    pub fn has_game_cycle(&self, ply: usize, cuckoo: &Cuckoo) -> bool {
+        let hash = self.zobrist_hash();
+        let occupied = self.bb_side[Sides::BOTH];
+        let limit = self.game_state.halfmove_clock.min(self.game_state.plies_from_null);
+
+        for distance in (3..=limit).step_by(2) {
+            let Some(key) = self.history.key(distance) else {
+                break;
+            };
+            let Some((from, to)) = cuckoo.find(hash ^ key) else {
+                continue;
+            };
+            if between(from, to) & occupied != 0 {
+                continue;
+            }
+            if distance < ply {
+                return true;
+            }
+
+            let square = if occupied & BB_SQUARES[from] != 0 { from } else { to };
+            if self.is_occupied(self.us(), square)
+                && (distance + 4..=limit)
+                    .step_by(2)
+                    .any(|earlier| self.history.key(earlier) == Some(key))
+            {
+                return true;
             }
         }
+        false
+    }
+
+    // A hundred plies without a capture or pawn move. A mate on the last of them still wins,
+    // which is up to the caller to check.
+    pub fn is_fifty_move_draw(&self) -> bool {
+        self.game_state.halfmove_clock >= 100
+    }
+
+    // No series of legal moves ends in mate: bare kings, a single minor piece, or only bishops
+    // that all stand on squares of one color
+    pub fn is_insufficient_material(&self) -> bool {
+        let both = |piece: Piece| {
+            self.bb_pieces[Sides::WHITE][piece] | self.bb_pieces[Sides::BLACK][piece]
+        };
+        if both(Pieces::PAWN) | both(Pieces::ROOK) | both(Pieces::QUEEN) != 0 {
+            return false;
+        }
+
+        let bishops = both(Pieces::BISHOP);
+        let knights = both(Pieces::KNIGHT);
+        (bishops | knights).count_ones() <= 1
+            || (knights == 0 && (bishops & LIGHT_SQUARES == 0 || bishops & !LIGHT_SQUARES == 0))
+    }
+
+    // How many times the current position has come up in the game, this time included
+    pub fn repetitions(&self) -> usize {
+        let hash = self.zobrist_hash();
+        let limit = self.game_state.halfmove_clock.min(self.game_state.plies_from_null);
+        let earlier = (4..=limit).step_by(2).filter(|&distance| {
+            self.history.key(distance) == Some(hash)
+        });
+        1 + earlier.count()
+    }
+
+    // How the game has ended over the board, None while it goes on. Only the automatic ends of
+    // the FIDE Laws count: threefold repetition and the fifty-move rule are draws a player has
+    // to claim, so the game goes on until fivefold repetition or seventy-five moves. Mate and
+    // stalemate come first, so a mate on the last move before that limit still wins.
+    pub fn game_result(&mut self, mg: &MoveGen) -> Option<GameResult> {
+        if mg.gen_legal_moves(self).index == 0 {
+            return Some(if mg.in_check(self, self.us()) {
+                GameResult::Checkmate(self.them())
+            } else {
+                GameResult::Stalemate
+            });
+        }
 
+        if self.is_insufficient_material() {
+            Some(GameResult::InsufficientMaterial)
+        } else if self.repetitions() >= 5 {
+            Some(GameResult::FivefoldRepetition)
+        } else if self.game_state.halfmove_clock >= 150 {
+            Some(GameResult::SeventyFiveMoves)
+        } else {
+            None
+        }
+    }
+
+    // Only the side to move, en passant and castling rights are added here, so changing those
+    // directly in game_state keeps the key right
+    pub fn zobrist_hash(&self) -> ZobristHash {
+        let mut zobrist_hash = self.piece_key;
         zobrist_hash ^= self.zobrist_randoms.rnd_side[self.game_state.active_color];
         match self.game_state.enpassant_piece {
             Some(square) => zobrist_hash ^= self.zobrist_randoms.rnd_en_passant[square],
@@ -281,11 +439,34 @@ impl Board {
         zobrist_hash
     }
 
+    fn compute_piece_key(&self) -> ZobristHash {
+        let mut piece_key: ZobristHash = 0;
+        for side in 0..2 {
+            for piece in 0..6 {
+                let mut current = self.bb_pieces[side][piece];
+                while let Some(square) = bitscan_forward(current) {
+                    current &= current - 1;
+                    piece_key ^= self.zobrist_randoms.rnd_pieces[side][piece][square];
+                }
+            }
+        }
+        piece_key
+    }
+
     pub fn undo_move(&mut self, _move: &Move) {
         self.history.decrement_hash(self.zobrist_hash());
 
         self.game_state = self.history.pop_entry();
 
+        if _move.is_castle() {
+            let (king_to, rook_to) = _move.castle_destinations();
+            self.remove_piece(king_to);
+            self.remove_piece(rook_to);
+            self.add_piece(_move.to(), Pieces::ROOK, self.us());
+            self.add_piece(_move.from(), Pieces::KING, self.us());
+            return;
+        }
+
         self.remove_piece(_move.to());
 
         // Non-enpassant capture
@@ -309,30 +490,6 @@ impl Board {
             }
         }
 
-        if _move.is_castle() {
-            let delta = _move.to() as isize - _move.from() as isize;
-            let long_castle = match delta {
-                -2 => true,
-                2 => false,
-                _ => panic!("Invalid castle in undo_move"),
-            };
-
-            let rook_from = if long_castle {
-                (_move.to() as isize - 2) as usize
-            } else {
-                (_move.to() as isize + 1) as usize
-            };
-
-            let rook_to = if long_castle {
-                (_move.to() as isize + 1) as usize
-            } else {
-                (_move.to() as isize - 1) as usize
-            };
-
-            self.remove_piece(rook_to);
-            self.add_piece(rook_from, Pieces::ROOK, self.us());
-        }
-
         self.add_piece(_move.from(), _move.piece(), self.us());
     }
 
@@ -341,17 +498,27 @@ impl Board {
         self.bb_side[side] |= 1 << square;
         self.bb_side[Sides::BOTH] |= 1 << square;
         self.piece_list[square] = Some(piece);
+        self.piece_key ^= self.zobrist_randoms.rnd_pieces[side][piece][square];
     }
 
     pub fn remove_piece(&mut self, square: Square) {
+        if let Some(piece) = self.piece_list[square] {
+            let side = if self.is_occupied(Sides::WHITE, square) {
+                Sides::WHITE
+            } else {
+                Sides::BLACK
+            };
+            self.piece_key ^= self.zobrist_randoms.rnd_pieces[side][piece][square];
+        }
+
         for side in 0..2 {
             for piece in 0..6 {
-                self.bb_pieces[side][piece] = self.bb_pieces[side][piece] & !(1 << square);
+                self.bb_pieces[side][piece] &= !(1 << square);
             }
         }
 
         for side in 0..3 {
-            self.bb_side[side] = self.bb_side[side] & !(1 << square);
+            self.bb_side[side] &= !(1 << square);
         }
         self.piece_list[square] = None;
     }
@@ -387,6 +554,145 @@ impl Board {
         }
     }
 
+    // FEN letter of the piece on a square, uppercase for white
+    pub fn piece_char(&self, square: Square) -> Option<char> {
+        let piece = self.get_piece_at(square)?;
+        if self.is_occupied(Sides::WHITE, square) {
+            Some(PIECE_CHARS[piece].to_ascii_uppercase())
+        } else {
+            Some(PIECE_CHARS[piece])
+        }
+    }
+
+    pub fn to_fen(&self) -> String {
+        let mut fen = String::new();
+
+        for rank in 0..8 {
+            let mut empty = 0;
+            for file in 0..8 {
+                match self.piece_char(rank * 8 + file) {
+                    Some(c) => {
+                        if empty > 0 {
+                            fen += &empty.to_string();
+                            empty = 0;
+                        }
+                        fen.push(c);
+                    }
+                    None => empty += 1,
+                }
+            }
+
+            if empty > 0 {
+                fen += &empty.to_string();
+            }
+            if rank < 7 {
+                fen.push('/');
+            }
+        }
+
+        fen += match self.us() {
+            Sides::WHITE => " w ",
+            _ => " b ",
+        };
+
+        let mut castling = String::new();
+        for side in [Sides::WHITE, Sides::BLACK] {
+            for long_castle in [false, true] {
+                if !self.game_state.can_castle(side, long_castle) {
+                    continue;
+                }
+
+                // The outermost rook is written as K/Q, inner Chess960 rooks by their file
+                let rook = self.castling_rooks[side][long_castle as usize];
+                let back_rank = rook - rook % 8;
+                let rooks = self.bb_pieces[side][Pieces::ROOK];
+                let mut outside = if long_castle {
+                    back_rank..rook
+                } else {
+                    rook + 1..back_rank + 8
+                };
+
+                let c = if outside.all(|square| rooks >> square & 1 == 0) {
+                    if long_castle {
+                        'q'
+                    } else {
+                        'k'
+                    }
+                } else {
+                    (b'a' + (rook % 8) as u8) as char
+                };
+
+                if side == Sides::WHITE {
+                    castling.push(c.to_ascii_uppercase());
+                } else {
+                    castling.push(c);
+                }
+            }
+        }
+        if castling.is_empty() {
+            fen.push('-');
+        } else {
+            fen += &castling;
+        }
+
+        match self.game_state.enpassant_piece {
+            Some(square) => fen += &format!(" {}", square_to_algebraic(square)),
+            None => fen += " -",
+        }
+
+        fen + &format!(
+            " {} {}",
+            self.game_state.halfmove_clock, self.game_state.fullmove_number
+        )
+    }
+
+    // The same position with colors swapped and the board flipped vertically
+    #[allow(clippy::needless_range_loop)]
+    pub fn mirrored(&self) -> Board {
+        let mut bb_pieces = [[EMPTY; 6]; 2];
+        for side in 0..2 {
+            for piece in 0..6 {
+                // Every byte is a rank, so swapping bytes mirrors the ranks
+                bb_pieces[side ^ 1][piece] = self.bb_pieces[side][piece].swap_bytes();
+            }
+        }
+
+        let mut piece_list: [Option<Piece>; 64] = [None; 64];
+        for square in 0..64 {
+            piece_list[square ^ 56] = self.piece_list[square];
+        }
+
+        let castling = self.game_state.castling_permissions;
+        let game_state = GameState {
+            active_color: self.them(),
+            castling_permissions: (castling & 0b11) << 2 | (castling >> 2 & 0b11),
+            enpassant_piece: self.game_state.enpassant_piece.map(|square| square ^ 56),
+            halfmove_clock: self.game_state.halfmove_clock,
+            fullmove_number: self.game_state.fullmove_number,
+            plies_from_null: 0,
+        };
+
+        let mut board = Board {
+            bb_pieces,
+            bb_side: [
+                self.bb_side[Sides::BLACK].swap_bytes(),
+                self.bb_side[Sides::WHITE].swap_bytes(),
+                self.bb_side[Sides::BOTH].swap_bytes(),
+            ],
+            game_state,
+            history: History::new(),
+            piece_list,
+            castling_rooks: [
+                self.castling_rooks[Sides::BLACK].map(|square| square ^ 56),
+                self.castling_rooks[Sides::WHITE].map(|square| square ^ 56),
+            ],
+            zobrist_randoms: self.zobrist_randoms.clone(),
+            piece_key: 0,
+        };
+        board.piece_key = board.compute_piece_key();
+        board
+    }
+
     pub fn from_fen(fen_string: &str) -> Result<Board, InvalidFenError> {
         let mut bb_pieces = [[EMPTY; 6]; 2];
         let parts: Vec<&str> = fen_string.split(' ').collect();
@@ -406,16 +712,21 @@ impl Board {
             _ => return Err(InvalidFenError::InvalidActiveColor),
         };
 
-        for rank in 0..8 {
+        for (rank, row) in ranks.iter().enumerate() {
             let mut file: usize = 0;
             // For each rank
-            for c in ranks[rank].chars() {
+            for c in row.chars() {
                 let square_index = file + (rank * 8);
 
-                if c.is_digit(10) {
+                if c.is_ascii_digit() {
                     file += c.to_digit(10).unwrap() as usize;
                     continue;
                 }
+
+                if file > 7 {
+                    return Err(InvalidFenError::InvalidRankLength);
+                }
+
                 match c {
                     'P' => bb_pieces[Sides::WHITE][Pieces::PAWN] |= BB_SQUARES[square_index],
                     'B' => bb_pieces[Sides::WHITE][Pieces::BISHOP] |= BB_SQUARES[square_index],
@@ -429,17 +740,28 @@ impl Board {
                     'r' => bb_pieces[Sides::BLACK][Pieces::ROOK] |= BB_SQUARES[square_index],
                     'q' => bb_pieces[Sides::BLACK][Pieces::QUEEN] |= BB_SQUARES[square_index],
                     'k' => bb_pieces[Sides::BLACK][Pieces::KING] |= BB_SQUARES[square_index],
-                    _ => (),
+                    _ => return Err(InvalidFenError::InvalidPiece),
                 }
 
                 file += 1;
             }
+
+            if file != 8 {
+                return Err(InvalidFenError::InvalidRankLength);
+            }
+        }
+
+        // Movegen assumes both kings are on the board
+        if bb_pieces[Sides::WHITE][Pieces::KING].count_ones() != 1
+            || bb_pieces[Sides::BLACK][Pieces::KING].count_ones() != 1
+        {
+            return Err(InvalidFenError::InvalidKingCount);
         }
 
         let mut piece_list: [Option<Piece>; 64] = [None; 64];
-        for side in 0..2 {
-            for piece in 0..6 {
-                let mut current = bb_pieces[side][piece];
+        for pieces in &bb_pieces {
+            for (piece, &bb) in pieces.iter().enumerate() {
+                let mut current = bb;
                 while let Some(square) = bitscan_forward(current) {
                     current &= current - 1;
                     piece_list[square] = Some(piece);
@@ -455,41 +777,112 @@ impl Board {
             bb_pieces.into_iter().flatten().fold(0, |a, b| a | b),
         ];
 
+        // Castling rooks default to the corners, X-FEN (KQkq) and Shredder-FEN (file letters)
+        // fields can place them elsewhere for Chess960
+        let mut castling_rooks = [[63, 56], [7, 0]];
         let mut castling_permissions = 0;
         for c in parts[2].chars() {
-            match c {
-                'K' => castling_permissions |= 1 << 0,
-                'Q' => castling_permissions |= 1 << 1,
-                'k' => castling_permissions |= 1 << 2,
-                'q' => castling_permissions |= 1 << 3,
-                '-' => (),
-                _ => return Err(InvalidFenError::InvalidCastlingPermission),
+            if c == '-' {
+                continue;
             }
+
+            let side = if c.is_ascii_uppercase() {
+                Sides::WHITE
+            } else {
+                Sides::BLACK
+            };
+            let back_rank = if side == Sides::WHITE { 56 } else { 0 };
+            let king = bb_pieces[side][Pieces::KING].trailing_zeros() as usize;
+            let rooks = bb_pieces[side][Pieces::ROOK];
+            if king / 8 != back_rank / 8 {
+                return Err(InvalidFenError::InvalidCastlingPermission);
+            }
+
+            let rook = match c.to_ascii_lowercase() {
+                // Outermost rook on that side of the king
+                'k' => (king + 1..back_rank + 8)
+                    .rev()
+                    .find(|&square| rooks >> square & 1 == 1),
+                'q' => (back_rank..king).find(|&square| rooks >> square & 1 == 1),
+                file @ 'a'..='h' => {
+                    let square = back_rank + (file as u8 - b'a') as usize;
+                    (rooks >> square & 1 == 1).then_some(square)
+                }
+                _ => None,
+            };
+            let Some(rook) = rook else {
+                return Err(InvalidFenError::InvalidCastlingPermission);
+            };
+
+            let long_castle = rook < king;
+            castling_rooks[side][long_castle as usize] = rook;
+            castling_permissions |= 1 << (side * 2 + long_castle as usize);
         }
 
         let mut enpassant_piece = None;
         if parts[3] != "-" {
-            enpassant_piece = Some(algebraic_to_square(parts[3]));
+            match parse_square(parts[3]) {
+                Some(square) => enpassant_piece = Some(square),
+                None => return Err(InvalidFenError::InvalidEnPassantSquare),
+            }
         }
 
+        // Optional, some GUIs leave the move counters out
+        let halfmove_clock = match parts.get(4).filter(|clock| !clock.is_empty()) {
+            Some(clock) => clock
+                .parse()
+                .map_err(|_| InvalidFenError::InvalidHalfmoveClock)?,
+            None => 0,
+        };
+        let fullmove_number = match parts.get(5).filter(|number| !number.is_empty()) {
+            Some(number) => number
+                .parse()
+                .map_err(|_| InvalidFenError::InvalidFullmoveNumber)?,
+            None => 1,
+        };
+
         let game_state: GameState = GameState {
             active_color,
             castling_permissions,
             enpassant_piece,
-            can_nullmove: true,
+            halfmove_clock,
+            // Some GUIs send 0, which would be written back as an invalid FEN
+            fullmove_number: fullmove_number.max(1),
+            plies_from_null: 0,
         };
 
         let history = History::new(); // Empty history, FEN doesn't give history information
 
         let zobrist_randoms = ZobristRandoms::new();
 
-        Ok(Board {
+        let mut board = Board {
             bb_pieces,
             bb_side,
             game_state,
             history,
             zobrist_randoms,
             piece_list,
-        })
+            castling_rooks,
+            piece_key: 0,
+        };
+        board.piece_key = board.compute_piece_key();
+        Ok(board)
+    }
+}
+
+impl fmt::Display for Board {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let separator = " +---+---+---+---+---+---+---+---+";
+
+        writeln!(f, "{}", separator)?;
+        for rank in 0..8 {
+            write!(f, " |")?;
+            for file in 0..8 {
+                write!(f, " {} |", self.piece_char(rank * 8 + file).unwrap_or(' '))?;
+            }
+            writeln!(f, " {}", 8 - rank)?;
+            writeln!(f, "{}", separator)?;
+        }
+        write!(f, "   a   b   c   d   e   f   g   h")
     }
 }
@@ -0,0 +1,101 @@
+use crate::movegen::{
+    movegen::{bishop_attacks, rook_attacks},
+    moves::LeapingMagics,
+};
+
+use super::{
+    board::ZobristRandoms,
+    defs::{Bitboard, Piece, Pieces, Square, ZobristHash, BB_SQUARES},
+};
+
+const CUCKOO_SIZE: usize = 8192;
+
+fn h1(key: ZobristHash) -> usize {
+    (key & 0x1fff) as usize
+}
+
+fn h2(key: ZobristHash) -> usize {
+    ((key >> 16) & 0x1fff) as usize
+}
+
+// Squares a piece attacks from `square` on an empty board, pawns never move back so they're left
+// out
+fn empty_board_attacks(piece: Piece, square: Square) -> Bitboard {
+    match piece {
+        Pieces::KNIGHT => LeapingMagics::KNIGHT[square] as Bitboard,
+        Pieces::KING => LeapingMagics::KING[square] as Bitboard,
+        Pieces::BISHOP => bishop_attacks(square, 0),
+        Pieces::ROOK => rook_attacks(square, 0),
+        Pieces::QUEEN => bishop_attacks(square, 0) | rook_attacks(square, 0),
+        _ => 0,
+    }
+}
+
+// Squares strictly between two squares on a line, none for anything else
+pub fn between(a: Square, b: Square) -> Bitboard {
+    let (bb_a, bb_b) = (BB_SQUARES[a], BB_SQUARES[b]);
+    if rook_attacks(a, 0) & bb_b != 0 {
+        rook_attacks(a, bb_b) & rook_attacks(b, bb_a)
+    } else if bishop_attacks(a, 0) & bb_b != 0 {
+        bishop_attacks(a, bb_b) & bishop_attacks(b, bb_a)
+    } else {
+        0
+    }
+}
+
+// Every reversible move of a piece between two squares, keyed by how it changes the Zobrist key.
+// XOR-ing the current key with an earlier one finds the move that would get back there, if any.
+// Marcel van Kervinck's cuckoo hashing keeps each lookup down to two probes.
+pub struct Cuckoo {
+    keys: Box<[ZobristHash; CUCKOO_SIZE]>,
+    moves: Box<[(Square, Square); CUCKOO_SIZE]>,
+}
+
+impl Cuckoo {
+    #[allow(clippy::needless_range_loop)]
+    pub fn new(randoms: &ZobristRandoms) -> Cuckoo {
+        let mut keys = Box::new([0; CUCKOO_SIZE]);
+        let mut moves = Box::new([(0, 0); CUCKOO_SIZE]);
+        let mut count = 0;
+
+        for side in 0..2 {
+            for piece in 0..6 {
+                for from in 0..64 {
+                    for to in from + 1..64 {
+                        if empty_board_attacks(piece, from) & BB_SQUARES[to] == 0 {
+                            continue;
+                        }
+
+                        let mut key = randoms.piece(side, piece, from)
+                            ^ randoms.piece(side, piece, to)
+                            ^ randoms.side_to_move();
+                        let mut squares = (from, to);
+
+                        // Swap into the first slot, moving whatever was there to its other slot
+                        let mut slot = h1(key);
+                        loop {
+                            std::mem::swap(&mut keys[slot], &mut key);
+                            std::mem::swap(&mut moves[slot], &mut squares);
+                            if key == 0 {
+                                break;
+                            }
+                            slot = if slot == h1(key) { h2(key) } else { h1(key) };
+                        }
+                        count += 1;
+                    }
+                }
+            }
+        }
+        debug_assert_eq!(count, 3668);
+
+        Cuckoo { keys, moves }
+    }
+
+    // Squares of the move that changes the key by `key`, either way round
+    pub fn find(&self, key: ZobristHash) -> Option<(Square, Square)> {
+        [h1(key), h2(key)]
+            .into_iter()
+            .find(|&slot| self.keys[slot] == key)
+            .map(|slot| self.moves[slot])
+    }
+}
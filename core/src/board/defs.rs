@@ -1,3 +1,5 @@
+use std::fmt;
+
 pub type Bitboard = u64;
 pub type ZobristHash = u64;
 pub type Piece = usize;
@@ -7,6 +9,10 @@ pub type Square = usize;
 pub const START_POS: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
 pub const EMPTY: Bitboard = 0;
 pub const BB_SQUARES: [u64; 64] = bb_squares();
+// a8 is a light square
+pub const LIGHT_SQUARES: Bitboard = 0xaa55aa55aa55aa55;
+pub const FILE_A: Bitboard = 0x0101010101010101;
+pub const FILE_H: Bitboard = FILE_A << 7;
 
 const fn bb_squares() -> [u64; 64] {
     let mut squares = [EMPTY; 64];
@@ -36,10 +42,75 @@ impl Sides {
     pub const BOTH: Side = 2;
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum InvalidFenError {
     InvalidPartCount,
     InvalidRankCount,
+    InvalidRankLength,
+    InvalidPiece,
+    InvalidKingCount,
     InvalidActiveColor,
     InvalidCastlingPermission,
+    InvalidEnPassantSquare,
+    InvalidHalfmoveClock,
+    InvalidFullmoveNumber,
+}
+
+impl fmt::Display for InvalidFenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let reason = match self {
+            InvalidFenError::InvalidPartCount => "expected at least 4 fields",
+            InvalidFenError::InvalidRankCount => "expected 8 ranks",
+            InvalidFenError::InvalidRankLength => "rank does not describe exactly 8 files",
+            InvalidFenError::InvalidPiece => "unknown piece character",
+            InvalidFenError::InvalidKingCount => "each side needs exactly one king",
+            InvalidFenError::InvalidActiveColor => "active color must be 'w' or 'b'",
+            InvalidFenError::InvalidCastlingPermission => "invalid castling field",
+            InvalidFenError::InvalidEnPassantSquare => "invalid en passant square",
+            InvalidFenError::InvalidHalfmoveClock => "invalid halfmove clock",
+            InvalidFenError::InvalidFullmoveNumber => "invalid fullmove number",
+        };
+        write!(f, "{}", reason)
+    }
+}
+
+// How a finished game ended
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GameResult {
+    // Won by the given side
+    Checkmate(Side),
+    Stalemate,
+    InsufficientMaterial,
+    FivefoldRepetition,
+    SeventyFiveMoves,
+}
+
+impl GameResult {
+    // Result tag of the game in PGN
+    pub fn pgn(&self) -> &'static str {
+        match self {
+            GameResult::Checkmate(Sides::WHITE) => "1-0",
+            GameResult::Checkmate(_) => "0-1",
+            _ => "1/2-1/2",
+        }
+    }
+
+    // Why the game ended, in words
+    pub fn reason(&self) -> &'static str {
+        match self {
+            GameResult::Checkmate(Sides::WHITE) => "White mates",
+            GameResult::Checkmate(_) => "Black mates",
+            GameResult::Stalemate => "Draw by stalemate",
+            GameResult::InsufficientMaterial => "Draw by insufficient material",
+            GameResult::FivefoldRepetition => "Draw by fivefold repetition",
+            GameResult::SeventyFiveMoves => "Draw by the seventy-five-move rule",
+        }
+    }
+}
+
+// The PGN result with the reason as a comment, the way the move text of a game ends
+impl fmt::Display for GameResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {{{}}}", self.pgn(), self.reason())
+    }
 }
@@ -1,3 +1,4 @@
+
 pub mod board;
 pub mod movegen;
 pub mod search;
@@ -1,3 +1,4 @@
+#[allow(clippy::module_inception)]
 pub mod movegen;
 pub mod moves;
 pub mod movelist;
\ No newline at end of file
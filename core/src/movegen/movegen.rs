@@ -4,7 +4,7 @@
 
 use crate::board::{
     board::Board,
-    defs::{Bitboard, Pieces, Side, Sides},
+    defs::{Bitboard, Pieces, Side, Sides, Square},
 };
 
 use super::{movelist::MoveList, moves::{LeapingMagics, Move}};
@@ -17,8 +17,75 @@ pub fn bitscan_forward(bb: u64) -> Option<usize> {
     }
 }
 
+// All squares from a to b inclusive, both on the same rank
+fn rank_span(a: Square, b: Square) -> Bitboard {
+    let (low, high) = (a.min(b), a.max(b));
+    (u64::MAX >> (63 - high)) & (u64::MAX << low)
+}
+
+const ORTHOGONAL: [(isize, isize); 4] = [(1, 0), (-1, 0), (0, 1), (0, -1)];
+const DIAGONAL: [(isize, isize); 4] = [(1, 1), (1, -1), (-1, 1), (-1, -1)];
+
+// Squares a slider on `square` reaches, stopping at (and including) the first occupied square
+fn sliding_attacks(square: Square, occupied: Bitboard, steps: &[(isize, isize)]) -> Bitboard {
+    let mut attacks = 0;
+    for &(file_step, rank_step) in steps {
+        let mut file = (square % 8) as isize + file_step;
+        let mut rank = (square / 8) as isize + rank_step;
+        while (0..8).contains(&file) && (0..8).contains(&rank) {
+            let target = (rank * 8 + file) as usize;
+            attacks |= 1 << target;
+            if occupied >> target & 1 == 1 {
+                break;
+            }
+            file += file_step;
+            rank += rank_step;
+        }
+    }
+
+    attacks
+}
+
+pub fn rook_attacks(square: Square, occupied: Bitboard) -> Bitboard {
+    sliding_attacks(square, occupied, &ORTHOGONAL)
+}
+
+pub fn bishop_attacks(square: Square, occupied: Bitboard) -> Bitboard {
+    sliding_attacks(square, occupied, &DIAGONAL)
+}
+
+// Squares a pawn of `side` on `square` attacks
+pub fn pawn_attacks(side: Side, square: Square) -> Bitboard {
+    let bb: Bitboard = 1 << square;
+    let not_a_file = 0xfefefefefefefefe;
+    let not_h_file = 0x7f7f7f7f7f7f7f7f;
+    match side {
+        Sides::WHITE => (bb >> 9 & not_h_file) | (bb >> 7 & not_a_file),
+        _ => (bb << 7 & not_h_file) | (bb << 9 & not_a_file),
+    }
+}
+
 pub struct MoveGen;
 impl MoveGen {
+    // Pieces of both sides attacking `square`, with sliders seeing through anything not in
+    // `occupied`
+    pub fn attackers_to(&self, board: &Board, square: Square, occupied: Bitboard) -> Bitboard {
+        let [white, black] = &board.bb_pieces;
+        let diagonal = white[Pieces::BISHOP]
+            | white[Pieces::QUEEN]
+            | black[Pieces::BISHOP]
+            | black[Pieces::QUEEN];
+        let orthogonal =
+            white[Pieces::ROOK] | white[Pieces::QUEEN] | black[Pieces::ROOK] | black[Pieces::QUEEN];
+
+        (pawn_attacks(Sides::BLACK, square) & white[Pieces::PAWN])
+            | (pawn_attacks(Sides::WHITE, square) & black[Pieces::PAWN])
+            | (LeapingMagics::KNIGHT[square] as u64 & (white[Pieces::KNIGHT] | black[Pieces::KNIGHT]))
+            | (LeapingMagics::KING[square] as u64 & (white[Pieces::KING] | black[Pieces::KING]))
+            | (bishop_attacks(square, occupied) & diagonal)
+            | (rook_attacks(square, occupied) & orthogonal)
+    }
+
     pub fn gen_pawn_moves(&self, board: &Board, moves: &mut MoveList) {
         // Step 1. Make function to return a forward bitboard for a given pawn
         let us = board.us();
@@ -170,16 +237,8 @@ impl MoveGen {
 
                     // Break if it went out of bounds
                     match offset.abs() {
-                        1 => {
-                            if square_rank != target_rank {
-                                break;
-                            }
-                        }
-                        8 => {
-                            if square_file != target_file {
-                                break;
-                            }
-                        }
+                        1 if square_rank != target_rank => break,
+                        8 if square_file != target_file => break,
                         _ => (),
                     }
 
@@ -220,17 +279,11 @@ impl MoveGen {
                     // Break if it went out of bounds
                     // Casts go crazy
                     match offset.abs() {
-                        7 => {
-                            if target_rank + target_file != square_rank + square_file {
-                                break;
-                            }
-                        }
-                        9 => {
-                            if target_rank as isize - target_file as isize
-                                != square_rank as isize - square_file as isize
-                            {
-                                break;
-                            }
+                        7 if target_rank + target_file != square_rank + square_file => break,
+                        9 if target_rank as isize - target_file as isize
+                            != square_rank as isize - square_file as isize =>
+                        {
+                            break
                         }
                         _ => (),
                     }
@@ -272,28 +325,14 @@ impl MoveGen {
                     // Break if it went out of bounds
                     // Casts go crazy
                     match offset.abs() {
-                        7 => {
-                            if target_rank + target_file != square_rank + square_file {
-                                break;
-                            }
-                        }
-                        9 => {
-                            if target_rank as isize - target_file as isize
-                                != square_rank as isize - square_file as isize
-                            {
-                                break;
-                            }
-                        }
-                        1 => {
-                            if square_rank != target_rank {
-                                break;
-                            }
-                        }
-                        8 => {
-                            if square_file != target_file {
-                                break;
-                            }
+                        7 if target_rank + target_file != square_rank + square_file => break,
+                        9 if target_rank as isize - target_file as isize
+                            != square_rank as isize - square_file as isize =>
+                        {
+                            break
                         }
+                        1 if square_rank != target_rank => break,
+                        8 if square_file != target_file => break,
                         _ => (),
                     }
 
@@ -350,32 +389,36 @@ impl MoveGen {
         let us = board.us();
         let them = board.them();
 
-        let our_king = bitscan_forward(board.get_pieces(us, Pieces::KING)).expect("King not found");
+        if !board.game_state.can_castle(us, false) && !board.game_state.can_castle(us, true) {
+            return;
+        }
 
+        let our_king = bitscan_forward(board.get_pieces(us, Pieces::KING)).expect("King not found");
         let attack_bb = self.gen_attack_bitboard(board, them);
 
-        // Short castle
-        if board.game_state.can_castle(us, false) {
-            if board.get_piece_at(our_king + 1).is_none()
-                && board.get_piece_at(our_king + 2).is_none()
-                && !self.in_check(board, us)
-            {
-                if (1 << our_king & attack_bb) == 0 && (1 << our_king + 1 & attack_bb) == 0 {
-                    moves.push(Move::new_castle(our_king, our_king + 2, board));
-                }
+        for long_castle in [false, true] {
+            if !board.game_state.can_castle(us, long_castle) {
+                continue;
             }
-        }
 
-        if board.game_state.can_castle(us, true) {
-            if board.get_piece_at(our_king - 1).is_none()
-                && board.get_piece_at(our_king - 2).is_none()
-                && board.get_piece_at(our_king - 3).is_none()
-                && !self.in_check(board, us)
-            {
-                if (1 << our_king & attack_bb) == 0 && (1 << our_king - 1 & attack_bb) == 0 {
-                    moves.push(Move::new_castle(our_king, our_king - 2, board));
-                }
+            // Works for Chess960 too, the king and rook may start anywhere on the back rank
+            let rook = board.castling_rooks[us][long_castle as usize];
+            let castle = Move::new_castle(our_king, rook);
+            let (king_to, rook_to) = castle.castle_destinations();
+
+            // Everything the king and rook pass over must be empty, apart from themselves
+            let blockers = board.bb_side[Sides::BOTH] & !(1 << our_king) & !(1 << rook);
+            let king_path = rank_span(our_king, king_to);
+            if (king_path | rank_span(rook, rook_to)) & blockers != 0 {
+                continue;
             }
+
+            // And the king can't start in, pass through or land in check
+            if king_path & attack_bb != 0 {
+                continue;
+            }
+
+            moves.push(castle);
         }
     }
 
@@ -404,8 +447,11 @@ impl MoveGen {
     }
 
     pub fn in_check(&self, board: &mut Board, side: Side) -> bool {
-        let king = board.get_pieces(side, Pieces::KING);
-        king & self.gen_attack_bitboard(board, side ^ 1) != 0
+        let Some(king) = bitscan_forward(board.get_pieces(side, Pieces::KING)) else {
+            return false;
+        };
+        let occupied = board.bb_side[Sides::BOTH];
+        self.attackers_to(board, king, occupied) & board.bb_side[side ^ 1] != 0
     }
 
     pub fn gen_moves(&self, board: &mut Board) -> MoveList {
@@ -422,13 +468,132 @@ impl MoveGen {
         moves
     }
 
-    pub fn gen_legal_moves_no_rep(&self, board: &mut Board) -> MoveList {
-        let count = board.history.count_hash(board.zobrist_hash());
-        if count >= 3 {
-            return MoveList::new();
+    // Pseudo-legal captures, en passant and promotions, found straight from the attack sets
+    // without generating any quiet moves
+    pub fn gen_captures(&self, board: &mut Board) -> MoveList {
+        let mut moves = MoveList::new();
+        let us = board.us();
+        let enemies = board.bb_side[board.them()];
+        let occupied = board.bb_side[Sides::BOTH];
+
+        let (forward, promotion_rank) = match us {
+            Sides::WHITE => (-8, 0),
+            _ => (8, 7),
+        };
+        let mut pawns = board.get_pieces(us, Pieces::PAWN);
+        while let Some(square) = bitscan_forward(pawns) {
+            pawns &= pawns - 1;
+
+            let attacks = pawn_attacks(us, square);
+            let mut targets = attacks & enemies;
+            let push = (square as isize + forward) as usize;
+            if push / 8 == promotion_rank && occupied >> push & 1 == 0 {
+                targets |= 1 << push;
+            }
+
+            while let Some(target) = bitscan_forward(targets) {
+                targets &= targets - 1;
+                if target / 8 == promotion_rank {
+                    for piece in [Pieces::QUEEN, Pieces::ROOK, Pieces::BISHOP, Pieces::KNIGHT] {
+                        moves.push(Move::new_promotion(square, target, board, piece));
+                    }
+                } else {
+                    moves.push(Move::new(square, target, board));
+                }
+            }
+
+            if let Some(target) = board.game_state.enpassant_piece {
+                if attacks >> target & 1 == 1 {
+                    moves.push(Move::new_enpassant(square, target, board));
+                }
+            }
+        }
+
+        for piece in [
+            Pieces::KNIGHT,
+            Pieces::BISHOP,
+            Pieces::ROOK,
+            Pieces::QUEEN,
+            Pieces::KING,
+        ] {
+            let mut pieces = board.get_pieces(us, piece);
+            while let Some(square) = bitscan_forward(pieces) {
+                pieces &= pieces - 1;
+
+                let attacks = match piece {
+                    Pieces::KNIGHT => LeapingMagics::KNIGHT[square] as Bitboard,
+                    Pieces::BISHOP => bishop_attacks(square, occupied),
+                    Pieces::ROOK => rook_attacks(square, occupied),
+                    Pieces::QUEEN => bishop_attacks(square, occupied) | rook_attacks(square, occupied),
+                    _ => LeapingMagics::KING[square] as Bitboard,
+                };
+                let mut targets = attacks & enemies;
+                while let Some(target) = bitscan_forward(targets) {
+                    targets &= targets - 1;
+                    moves.push(Move::new(square, target, board));
+                }
+            }
+        }
+
+        moves
+    }
+
+    // Pseudo-legal moves that gen_captures leaves out, castles included
+    pub fn gen_quiets(&self, board: &mut Board) -> MoveList {
+        let mut moves = MoveList::new();
+        for mv in self.gen_moves(board).iter() {
+            if !mv.is_tactical() {
+                moves.push(*mv);
+            }
+        }
+
+        moves
+    }
+
+    // Whether `mv`, e.g. from the transposition table or a killer slot, can be played in this
+    // position, ignoring checks. Only the moves of the piece that moves are generated.
+    pub fn is_pseudo_legal(&self, board: &mut Board, mv: Move) -> bool {
+        if board.get_pieces(board.us(), mv.piece()) & (1 << mv.from()) == 0 {
+            return false;
+        }
+
+        let mut moves = MoveList::new();
+        if mv.is_castle() {
+            self.gen_castle_moves(board, &mut moves);
+        } else {
+            match mv.piece() {
+                Pieces::PAWN => self.gen_pawn_moves(board, &mut moves),
+                Pieces::KNIGHT => self.gen_knight_moves(board, &mut moves),
+                Pieces::BISHOP => self.gen_bishop_moves(board, &mut moves),
+                Pieces::ROOK => self.gen_rook_moves(board, &mut moves),
+                Pieces::QUEEN => self.gen_queen_moves(board, &mut moves),
+                _ => self.gen_king_moves(board, &mut moves),
+            }
+        }
+
+        let found = moves.iter().any(|&generated| generated == mv);
+        found
+    }
+
+    // Counts the leaf nodes of the legal move tree, used to verify move generation
+    pub fn perft(&self, board: &mut Board, depth: usize) -> usize {
+        if depth == 0 {
+            return 1;
+        }
+
+        let moves = self.gen_legal_moves(board);
+        if depth == 1 {
+            return moves.index;
+        }
+
+        let mut count = 0;
+        for mv in moves.iter() {
+            board.do_move(mv);
+            count += self.perft(board, depth - 1);
+            board.undo_move(mv);
         }
 
-        self.gen_legal_moves(board)
+        count
     }
 
     pub fn gen_legal_moves(&self, board: &mut Board) -> MoveList {
@@ -7,6 +7,12 @@ pub struct MoveList {
     pub index: usize
 }
 
+impl Default for MoveList {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl MoveList {
     pub fn push(&mut self, mv: Move) {
         self.moves[self.index] = mv;
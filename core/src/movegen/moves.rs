@@ -71,24 +71,15 @@ impl Move {
         Move(data)
     }
 
-    pub fn new_castle(from: usize, to: usize, board: &Board) -> Move {
+    // Castles are encoded as the king (from) taking its own rook (to)
+    pub fn new_castle(from: usize, to: usize) -> Move {
         let mut data: usize = 0;
-        let capture = board.get_piece_at(to);
-        // This should NEVER happen
-        let piece = board
-            .get_piece_at(from)
-            .expect("Passed move from empty board piece");
 
         data |= from << Shift::FROM;
         data |= to << Shift::TO;
-        data |= piece << Shift::PIECE;
+        data |= Pieces::KING << Shift::PIECE;
         data |= 1 << Shift::CASTLE;
-
-        // None is represented as 7 in this structure
-        match capture {
-            None => data |= 7 << Shift::CAPTURE,
-            Some(cap) => data |= cap << Shift::CAPTURE,
-        }
+        data |= 7 << Shift::CAPTURE;
 
         Move(data)
     }
@@ -120,6 +111,25 @@ impl Move {
         self.0 >> Shift::CASTLE & 1 == 1
     }
 
+    // Final king and rook squares of a castle, the same as in standard chess
+    pub fn castle_destinations(&self) -> (Square, Square) {
+        let back_rank = self.from() - self.from() % 8;
+        if self.to() > self.from() {
+            (back_rank + 6, back_rank + 5)
+        } else {
+            (back_rank + 2, back_rank + 3)
+        }
+    }
+
+    // Square the moved piece ends up on, for castles that is the king's square
+    pub fn destination(&self) -> Square {
+        if self.is_castle() {
+            self.castle_destinations().0
+        } else {
+            self.to()
+        }
+    }
+
     pub fn is_promotion(&self) -> bool {
         self.0 >> Shift::PROMOTION & 1 == 1
     }
@@ -149,17 +159,49 @@ impl Move {
         }
     }
 
+    // Captures, en passant included, and promotions
+    pub fn is_tactical(&self) -> bool {
+        self.capture().is_some() || self.is_enpassant() || self.is_promotion()
+    }
+
     pub fn is_double_hop(&self) -> bool {
-        if (self.from() as isize - self.to() as isize).abs() == 16 {
-            true
-        } else {
-            false
-        }
+        (self.from() as isize - self.to() as isize).abs() == 16
     }
 
     pub fn is_enpassant(&self) -> bool {
         self.0 >> Shift::ENPASSANT & 1 == 1
     }
+
+    // 16 bit form for the transposition table, only from, to and promotion. The rest is implied
+    // by the position the move is played in. 0 is never a move, as from and to always differ.
+    pub fn compact(&self) -> u16 {
+        let mut data = self.from() | self.to() << 6;
+        if self.is_promotion() {
+            data |= 1 << 12 | (self.promotion_piece() - 1) << 13;
+        }
+        data as u16
+    }
+
+    // Rebuilds a compact move in the position it was stored for. Moves that don't fit the
+    // position come out as something illegal, so they still have to be checked.
+    pub fn from_compact(compact: u16, board: &Board) -> Option<Move> {
+        let from = compact as usize & 0x3f;
+        let to = compact as usize >> 6 & 0x3f;
+        let piece = board.get_piece_at(from)?;
+        if compact == 0 || !board.is_occupied(board.us(), from) {
+            return None;
+        }
+
+        Some(if compact >> 12 & 1 == 1 {
+            Move::new_promotion(from, to, board, (compact as usize >> 13 & 0b11) + 1)
+        } else if piece == Pieces::KING && board.is_occupied(board.us(), to) {
+            Move::new_castle(from, to)
+        } else if piece == Pieces::PAWN && board.is_enpassant(to) {
+            Move::new_enpassant(from, to, board)
+        } else {
+            Move::new(from, to, board)
+        })
+    }
 }
 
 pub struct LeapingMagics;
@@ -1,4 +1,10 @@
 pub mod defs;
+pub mod heuristics;
+pub mod movepicker;
+pub mod params;
+#[allow(clippy::module_inception)]
 pub mod search;
+pub mod see;
 pub mod sorting;
+pub mod stack;
 pub mod ttable;
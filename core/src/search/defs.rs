@@ -1,6 +1,51 @@
+use std::time::Duration;
+
+use crate::movegen::moves::Move;
+
 pub struct PieceTables;
 
-pub const INF: i32 = i32::MAX - 1;
+// Fits the transposition table's 16 bit scores
+pub const INF: i32 = 32000;
+pub const PIECE_VALUES: [i32; 6] = [100, 320, 300, 500, 900, 0];
+pub const MAX_DEPTH: usize = 64;
+// Share of the evaluation kept in an ordinary position, drawish endings get less
+pub const SCALE_NORMAL: i32 = 128;
+
+// What a reported score says about the real one
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ScoreBound {
+    Exact,
+    // Failed high, the score is at least this
+    Lower,
+    // Failed low, the score is at most this
+    Upper,
+}
+
+// Reported after every completed iteration of iterative deepening
+pub struct SearchInfo {
+    pub depth: usize,
+    pub score: i32,
+    pub bound: ScoreBound,
+    pub nodes: u64,
+    // Permille of the transposition table in use
+    pub hashfull: usize,
+    pub time: Duration,
+    pub pv: Vec<Move>,
+}
+
+// Static evaluation split into its terms, all from white's point of view
+pub struct EvalBreakdown {
+    pub material: i32,
+    pub psqt: i32,
+    pub double_pawns: i32,
+    // 0 is the opening, 256 a bare endgame
+    pub phase: i32,
+    // Out of SCALE_NORMAL, applied to the sum of the terms
+    pub scale: i32,
+    pub total: i32,
+}
+
+pub type InfoCallback = Box<dyn FnMut(&SearchInfo) + Send>;
 
 #[rustfmt::skip]
 impl PieceTables {
@@ -0,0 +1,157 @@
+use crate::{
+    board::defs::{Piece, Pieces, Side},
+    movegen::moves::Move,
+};
+
+// History scores stay within +-MAX_HISTORY thanks to the gravity formula
+pub const MAX_HISTORY: i32 = 16384;
+
+// Continuation history, indexed by the colored piece and destination of an earlier move and then
+// of the move that followed it
+pub type ContinuationHistory = [[[[i32; 64]; 12]; 64]; 12];
+
+// Move ordering state, learned during search and reset with `Search::clear`
+pub struct Heuristics {
+    // Butterfly table indexed by side, from and to square
+    pub history: Box<[[[i32; 64]; 64]; 2]>,
+    // Quiet reply that refuted a move, indexed by that move's colored piece and destination
+    pub countermoves: [[Option<Move>; 64]; 12],
+    // How quiet moves did after the moves one and two plies before them
+    pub continuation: Box<ContinuationHistory>,
+    // Captures indexed by the capturing piece, destination and captured piece
+    pub capture_history: Box<[[[i32; 6]; 64]; 6]>,
+}
+
+impl Default for Heuristics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Heuristics {
+    pub fn new() -> Heuristics {
+        Heuristics {
+            history: Box::new([[[0; 64]; 64]; 2]),
+            countermoves: [[None; 64]; 12],
+            // Too big to build on the stack first
+            continuation: vec![[[[0; 64]; 12]; 64]; 12].into_boxed_slice().try_into().unwrap(),
+            capture_history: Box::new([[[0; 6]; 64]; 6]),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        *self.history = [[[0; 64]; 64]; 2];
+        self.countermoves = [[None; 64]; 12];
+        self.continuation.iter_mut().flatten().flatten().for_each(|row| *row = [0; 64]);
+        *self.capture_history = [[[0; 6]; 64]; 6];
+    }
+
+    pub fn history(&self, side: Side, mv: Move) -> i32 {
+        self.history[side][mv.from()][mv.to()]
+    }
+
+    // Reply for `side` to the move the other side just made
+    pub fn countermove(&self, side: Side, previous: Option<Move>) -> Option<Move> {
+        previous.and_then(|prev| self.countermoves[colored(side ^ 1, prev.piece())][prev.to()])
+    }
+
+    // How `mv` by `side` did after the moves one and two plies back, the first one made by the
+    // other side and the second one by `side`
+    pub fn continuation(&self, side: Side, previous: [Option<Move>; 2], mv: Move) -> i32 {
+        continuations(side, previous)
+            .map(|(prev, prev_side)| {
+                self.continuation[colored(prev_side, prev.piece())][prev.to()]
+                    [colored(side, mv.piece())][mv.to()]
+            })
+            .sum()
+    }
+
+    // Everything known about a quiet move, `previous` holds the moves one and two plies back
+    pub fn quiet_history(&self, side: Side, mv: Move, previous: [Option<Move>; 2]) -> i32 {
+        self.history(side, mv) + self.continuation(side, previous, mv)
+    }
+
+    // 0 for quiet promotions, which have nothing to capture
+    pub fn capture_history(&self, mv: Move) -> i32 {
+        captured(mv).map_or(0, |piece| self.capture_history[mv.piece()][mv.to()][piece])
+    }
+
+    // Called when quiet move `mv` caused a beta cutoff, after `failed` quiets were searched
+    // without one. `previous` holds the moves one and two plies back.
+    pub fn update_cutoff(
+        &mut self,
+        side: Side,
+        depth: usize,
+        mv: Move,
+        previous: [Option<Move>; 2],
+        failed: &[Move],
+    ) {
+        if let Some(prev) = previous[0] {
+            self.countermoves[colored(side ^ 1, prev.piece())][prev.to()] = Some(mv);
+        }
+
+        let bonus = history_bonus(depth);
+        self.update_quiet(side, mv, previous, bonus);
+        for &quiet in failed {
+            self.update_quiet(side, quiet, previous, -bonus);
+        }
+    }
+
+    // Called on every beta cutoff with the captures searched without one, and the move that
+    // caused it when that was a capture too
+    pub fn update_captures(&mut self, depth: usize, mv: Option<Move>, failed: &[Move]) {
+        let bonus = history_bonus(depth);
+        if let Some(mv) = mv {
+            self.update_capture(mv, bonus);
+        }
+        for &capture in failed {
+            self.update_capture(capture, -bonus);
+        }
+    }
+
+    fn update_quiet(&mut self, side: Side, mv: Move, previous: [Option<Move>; 2], bonus: i32) {
+        gravity(&mut self.history[side][mv.from()][mv.to()], bonus);
+        for (prev, prev_side) in continuations(side, previous) {
+            let entry = &mut self.continuation[colored(prev_side, prev.piece())][prev.to()]
+                [colored(side, mv.piece())][mv.to()];
+            gravity(entry, bonus);
+        }
+    }
+
+    fn update_capture(&mut self, mv: Move, bonus: i32) {
+        if let Some(piece) = captured(mv) {
+            gravity(&mut self.capture_history[mv.piece()][mv.to()][piece], bonus);
+        }
+    }
+}
+
+// Index of a piece of either side, so the same piece type of both sides gets its own entries
+fn colored(side: Side, piece: Piece) -> usize {
+    side * 6 + piece
+}
+
+// The earlier moves that are there, with the side that made each
+fn continuations(side: Side, previous: [Option<Move>; 2]) -> impl Iterator<Item = (Move, Side)> {
+    previous
+        .into_iter()
+        .zip([side ^ 1, side])
+        .filter_map(|(prev, prev_side)| Some((prev?, prev_side)))
+}
+
+fn history_bonus(depth: usize) -> i32 {
+    (depth * depth).min(1200) as i32
+}
+
+// Moves the entry towards the bonus, shrinking it the closer it already is to the limit
+fn gravity(entry: &mut i32, bonus: i32) {
+    *entry += bonus - *entry * bonus.abs() / MAX_HISTORY;
+}
+
+// En passant doesn't record the pawn it captures
+fn captured(mv: Move) -> Option<Piece> {
+    if mv.is_enpassant() {
+        Some(Pieces::PAWN)
+    } else {
+        mv.capture()
+    }
+}
@@ -0,0 +1,225 @@
+use crate::{
+    board::{board::Board, defs::Pieces},
+    movegen::{
+        movegen::MoveGen,
+        movelist::{MoveList, MAX_LEGAL_MOVES},
+        moves::Move,
+    },
+};
+
+use super::{
+    heuristics::Heuristics,
+    see::see_ge,
+    sorting::{score_capture, score_quiet},
+};
+
+fn is_queen_promotion(mv: Move) -> bool {
+    mv.is_promotion() && mv.promotion_piece() == Pieces::QUEEN
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Stage {
+    HashMove,
+    GenCaptures,
+    GoodCaptures,
+    Killers,
+    Countermove,
+    GenQuiets,
+    Quiets,
+    BadCaptures,
+    Done,
+}
+
+// Hands out pseudo-legal moves one at a time, best first, generating each kind of move only once
+// the previous stages are used up. A cutoff on the hash move never generates anything.
+pub struct MovePicker {
+    pub stage: Stage,
+    hash_move: Option<Move>,
+    killers: [Option<Move>; 2],
+    countermove: Option<Move>,
+    // Moves one and two plies back, for the continuation history
+    previous: [Option<Move>; 2],
+    // Moves of the current generation stage, with the ones before `index` already handed out
+    moves: MoveList,
+    scores: [i32; MAX_LEGAL_MOVES],
+    index: usize,
+    // Captures that failed SEE during the good capture stage, tried last
+    bad_captures: MoveList,
+    bad_index: usize,
+    killer_index: usize,
+    // Quiescence search only wants the good captures
+    captures_only: bool,
+    // Evaluation's piece values, for SEE
+    piece_values: [i32; 6],
+}
+
+impl MovePicker {
+    pub fn new(
+        board: &mut Board,
+        mg: &MoveGen,
+        hash_move: Option<Move>,
+        heuristics: &Heuristics,
+        killers: [Option<Move>; 2],
+        previous: [Option<Move>; 2],
+        piece_values: [i32; 6],
+    ) -> MovePicker {
+        let hash_move = hash_move.filter(|&mv| mg.is_pseudo_legal(board, mv));
+        let us = board.us();
+
+        // Refutations only make sense as quiet moves, anything else comes from other stages
+        let mut refutation = |mv: Option<Move>| {
+            mv.filter(|&mv| {
+                Some(mv) != hash_move && !mv.is_tactical() && mg.is_pseudo_legal(board, mv)
+            })
+        };
+        let killers = killers.map(&mut refutation);
+        let countermove = refutation(heuristics.countermove(us, previous[0]))
+            .filter(|&mv| !killers.contains(&Some(mv)));
+
+        MovePicker {
+            stage: Stage::HashMove,
+            hash_move,
+            killers,
+            countermove,
+            previous,
+            moves: MoveList::new(),
+            scores: [0; MAX_LEGAL_MOVES],
+            index: 0,
+            bad_captures: MoveList::new(),
+            bad_index: 0,
+            killer_index: 0,
+            captures_only: false,
+            piece_values,
+        }
+    }
+
+    // Hash move and captures that don't lose material, promotions included
+    pub fn new_quiescence(
+        board: &mut Board,
+        mg: &MoveGen,
+        hash_move: Option<Move>,
+        piece_values: [i32; 6],
+    ) -> MovePicker {
+        let hash_move = hash_move.filter(|&mv| mv.is_tactical() && mg.is_pseudo_legal(board, mv));
+
+        MovePicker {
+            stage: Stage::HashMove,
+            hash_move,
+            killers: [None; 2],
+            countermove: None,
+            previous: [None; 2],
+            moves: MoveList::new(),
+            scores: [0; MAX_LEGAL_MOVES],
+            index: 0,
+            bad_captures: MoveList::new(),
+            bad_index: 0,
+            killer_index: 0,
+            captures_only: true,
+            piece_values,
+        }
+    }
+
+    pub fn next(&mut self, board: &mut Board, mg: &MoveGen, heuristics: &Heuristics) -> Option<Move> {
+        loop {
+            match self.stage {
+                Stage::HashMove => {
+                    self.stage = Stage::GenCaptures;
+                    if self.hash_move.is_some() {
+                        return self.hash_move;
+                    }
+                }
+                Stage::GenCaptures => {
+                    self.moves = mg.gen_captures(board);
+                    self.index = 0;
+                    self.score_captures(heuristics);
+                    self.stage = Stage::GoodCaptures;
+                }
+                // Queen promotions are never put off as bad captures, quiescence drops those
+                Stage::GoodCaptures => match self.select_best() {
+                    Some(mv) if Some(mv) == self.hash_move => (),
+                    Some(mv)
+                        if !is_queen_promotion(mv)
+                            && !see_ge(mg, board, mv, 0, &self.piece_values) =>
+                    {
+                        self.bad_captures.push(mv)
+                    }
+                    Some(mv) => return Some(mv),
+                    None if self.captures_only => self.stage = Stage::Done,
+                    None => self.stage = Stage::Killers,
+                },
+                Stage::Killers => {
+                    let killer = self.killers.get(self.killer_index).copied();
+                    self.killer_index += 1;
+                    match killer {
+                        Some(Some(mv)) => return Some(mv),
+                        Some(None) => (),
+                        None => self.stage = Stage::Countermove,
+                    }
+                }
+                Stage::Countermove => {
+                    self.stage = Stage::GenQuiets;
+                    if self.countermove.is_some() {
+                        return self.countermove;
+                    }
+                }
+                Stage::GenQuiets => {
+                    self.moves = mg.gen_quiets(board);
+                    self.index = 0;
+                    self.score_quiets(board, heuristics);
+                    self.stage = Stage::Quiets;
+                }
+                Stage::Quiets => match self.select_best() {
+                    Some(mv) if self.is_refutation(mv) => (),
+                    Some(mv) => return Some(mv),
+                    None => self.stage = Stage::BadCaptures,
+                },
+                Stage::BadCaptures => {
+                    if self.bad_index < self.bad_captures.index {
+                        self.bad_index += 1;
+                        return Some(self.bad_captures.moves[self.bad_index - 1]);
+                    }
+                    self.stage = Stage::Done;
+                }
+                Stage::Done => return None,
+            }
+        }
+    }
+
+    // Already handed out by an earlier stage
+    fn is_refutation(&self, mv: Move) -> bool {
+        Some(mv) == self.hash_move || self.killers.contains(&Some(mv)) || Some(mv) == self.countermove
+    }
+
+    fn score_captures(&mut self, heuristics: &Heuristics) {
+        for i in 0..self.moves.index {
+            self.scores[i] = score_capture(self.moves.moves[i], heuristics);
+        }
+    }
+
+    fn score_quiets(&mut self, board: &Board, heuristics: &Heuristics) {
+        for i in 0..self.moves.index {
+            let mv = self.moves.moves[i];
+            self.scores[i] = score_quiet(mv, board.us(), heuristics, self.previous);
+        }
+    }
+
+    // Selection sort step, cheaper than sorting when a cutoff comes early
+    fn select_best(&mut self) -> Option<Move> {
+        if self.index >= self.moves.index {
+            return None;
+        }
+
+        let mut best = self.index;
+        for i in self.index + 1..self.moves.index {
+            if self.scores[i] > self.scores[best] {
+                best = i;
+            }
+        }
+
+        self.moves.moves.swap(self.index, best);
+        self.scores.swap(self.index, best);
+        self.index += 1;
+
+        Some(self.moves.moves[self.index - 1])
+    }
+}
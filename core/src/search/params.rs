@@ -0,0 +1,253 @@
+use super::defs::PIECE_VALUES;
+
+// Values of SearchParams::internal_iteration
+pub const INTERNAL_OFF: usize = 0;
+pub const INTERNAL_REDUCTIONS: usize = 1;
+pub const INTERNAL_DEEPENING: usize = 2;
+// Names of those values, indexed by value
+pub const INTERNAL_MODES: [&str; 3] = ["Off", "Reductions", "Deepening"];
+
+// Search features that can be switched off or tuned, mostly to test them against each other
+#[derive(Clone, Debug, PartialEq)]
+pub struct SearchParams {
+    // Moves that give check are searched one ply deeper
+    pub check_extension: bool,
+    // The hash move is extended when every other move fails well below its score
+    pub singular_extension: bool,
+    // Recapturing on the square of the last capture, in PV nodes
+    pub recapture_extension: bool,
+    // Pawn pushes to the seventh rank, one step before promoting
+    pub pawn_push_extension: bool,
+    // Extensions allowed along one line from the root, so they can't go on forever
+    pub extension_budget: usize,
+    // Singular extensions are only tried this deep, where the verification search pays off
+    pub singular_min_depth: usize,
+    // Per ply of depth, how far below the hash score the other moves have to stay
+    pub singular_margin: i32,
+    // Razoring, nodes this shallow whose eval is further below alpha than base + margin * depth
+    // drop into quiescence
+    pub razor_max_depth: usize,
+    pub razor_base: i32,
+    pub razor_margin: i32,
+    // Futility pruning of quiet moves, with the same kind of margin
+    pub futility_max_depth: usize,
+    pub futility_base: i32,
+    pub futility_margin: i32,
+    // Late move pruning, quiets after the first base + depth * depth moves are skipped, half as
+    // many unless the position is improving
+    pub lmp_max_depth: usize,
+    pub lmp_base: usize,
+    // History pruning, quiets with a history below -margin * depth are skipped
+    pub history_prune_max_depth: usize,
+    pub history_prune_margin: i32,
+    // Quiescence captures that can't get within this of alpha, even winning the piece for free,
+    // are skipped
+    pub delta_margin: i32,
+    // Initial half width of the aspiration window, doubled after every fail
+    pub aspiration_window: i32,
+    // Reverse futility pruning, a node whose eval beats beta by margin * depth returns early,
+    // a ply less of margin when improving
+    pub rfp_margin: i32,
+    // Null move pruning, the null move search is reduced by reduction + depth / depth_divisor
+    // plies, plus one per eval_divisor the eval is above beta, at most 3. Cutoffs are verified
+    // from verify_depth on.
+    pub null_move_reduction: usize,
+    pub null_move_depth_divisor: usize,
+    pub null_move_eval_divisor: i32,
+    pub null_verify_depth: usize,
+    // Late move reduction, (base + ln(depth) * ln(move count) * factor) / 100 plies, one ply
+    // less for every divisor of history score
+    pub lmr_base: i32,
+    pub lmr_factor: i32,
+    pub lmr_history_divisor: i32,
+    // SEE pruning, quiets losing more than quiet_margin * depth^2 and captures losing more than
+    // capture_margin * depth are skipped
+    pub see_prune_max_depth: usize,
+    pub see_quiet_margin: i32,
+    pub see_capture_margin: i32,
+    // What to do in a node without a hash move, nothing, reduce its depth by one from
+    // iir_min_depth on, or in PV nodes from iid_min_depth on search it 2 plies shallower first
+    pub internal_iteration: usize,
+    pub iir_min_depth: usize,
+    pub iid_min_depth: usize,
+}
+
+impl Default for SearchParams {
+    fn default() -> Self {
+        SearchParams {
+            check_extension: true,
+            singular_extension: true,
+            recapture_extension: true,
+            pawn_push_extension: true,
+            extension_budget: 16,
+            singular_min_depth: 8,
+            singular_margin: 2,
+            razor_max_depth: 3,
+            razor_base: 250,
+            razor_margin: 200,
+            futility_max_depth: 6,
+            futility_base: 80,
+            futility_margin: 100,
+            lmp_max_depth: 8,
+            lmp_base: 3,
+            history_prune_max_depth: 3,
+            history_prune_margin: 2000,
+            delta_margin: 200,
+            aspiration_window: 25,
+            rfp_margin: 150,
+            null_move_reduction: 3,
+            null_move_depth_divisor: 3,
+            null_move_eval_divisor: 200,
+            null_verify_depth: 12,
+            lmr_base: 278,
+            lmr_factor: 40,
+            lmr_history_divisor: 8192,
+            see_prune_max_depth: 8,
+            see_quiet_margin: 20,
+            see_capture_margin: 100,
+            internal_iteration: INTERNAL_REDUCTIONS,
+            iir_min_depth: 4,
+            iid_min_depth: 6,
+        }
+    }
+}
+
+impl SearchParams {
+    // On/off switches by name, as exposed over UCI
+    pub fn toggles(&mut self) -> [(&'static str, &mut bool); 4] {
+        [
+            ("CheckExtension", &mut self.check_extension),
+            ("SingularExtension", &mut self.singular_extension),
+            ("RecaptureExtension", &mut self.recapture_extension),
+            ("PawnPushExtension", &mut self.pawn_push_extension),
+        ]
+    }
+
+    // Settings picked by name from a list, stored as the index of the choice. Exposed over UCI as
+    // combos, and not to SPSA which can only tune numbers with an order.
+    pub fn choices(&mut self) -> [(&'static str, &'static [&'static str], &mut usize); 1] {
+        [("InternalIteration", &INTERNAL_MODES, &mut self.internal_iteration)]
+    }
+
+    // Numeric parameters by name, for UCI and SPSA tuning
+    pub fn tunables(&mut self) -> Vec<Tunable<'_>> {
+        vec![
+            Tunable::depth("SingularMinDepth", &mut self.singular_min_depth, 4, 12, 1),
+            Tunable::int("SingularMargin", &mut self.singular_margin, 1, 6, 1),
+            Tunable::depth("RazorMaxDepth", &mut self.razor_max_depth, 1, 6, 1),
+            Tunable::int("RazorBase", &mut self.razor_base, 50, 500, 25),
+            Tunable::int("RazorMargin", &mut self.razor_margin, 50, 400, 20),
+            Tunable::depth("FutilityMaxDepth", &mut self.futility_max_depth, 1, 10, 1),
+            Tunable::int("FutilityBase", &mut self.futility_base, 0, 200, 10),
+            Tunable::int("FutilityMargin", &mut self.futility_margin, 30, 250, 10),
+            Tunable::depth("LmpMaxDepth", &mut self.lmp_max_depth, 2, 12, 1),
+            Tunable::depth("LmpBase", &mut self.lmp_base, 1, 8, 1),
+            Tunable::depth("HistoryPruneMaxDepth", &mut self.history_prune_max_depth, 1, 6, 1),
+            Tunable::int("HistoryPruneMargin", &mut self.history_prune_margin, 500, 6000, 250),
+            Tunable::int("DeltaMargin", &mut self.delta_margin, 50, 500, 25),
+            Tunable::int("AspirationWindow", &mut self.aspiration_window, 5, 100, 5),
+            Tunable::int("RfpMargin", &mut self.rfp_margin, 50, 300, 10),
+            Tunable::depth("NullMoveReduction", &mut self.null_move_reduction, 1, 6, 1),
+            Tunable::depth("NullMoveDepthDivisor", &mut self.null_move_depth_divisor, 2, 8, 1),
+            Tunable::int("NullMoveEvalDivisor", &mut self.null_move_eval_divisor, 50, 400, 20),
+            Tunable::depth("NullVerifyDepth", &mut self.null_verify_depth, 6, 20, 1),
+            Tunable::int("LmrBase", &mut self.lmr_base, 0, 500, 20),
+            Tunable::int("LmrFactor", &mut self.lmr_factor, 10, 100, 5),
+            Tunable::int("LmrHistoryDivisor", &mut self.lmr_history_divisor, 2048, 16384, 512),
+            Tunable::depth("SeePruneMaxDepth", &mut self.see_prune_max_depth, 2, 12, 1),
+            Tunable::int("SeeQuietMargin", &mut self.see_quiet_margin, 5, 60, 3),
+            Tunable::int("SeeCaptureMargin", &mut self.see_capture_margin, 30, 200, 10),
+            Tunable::depth("IirMinDepth", &mut self.iir_min_depth, 2, 10, 1),
+            Tunable::depth("IidMinDepth", &mut self.iid_min_depth, 3, 12, 1),
+        ]
+    }
+}
+
+// Weights of the static evaluation
+#[derive(Clone, Debug, PartialEq)]
+pub struct EvalParams {
+    // Material, indexed by piece, the king is always 0
+    pub piece_values: [i32; 6],
+    // For each file with more than one pawn of a side
+    pub double_pawn_penalty: i32,
+}
+
+impl Default for EvalParams {
+    fn default() -> Self {
+        EvalParams {
+            piece_values: PIECE_VALUES,
+            double_pawn_penalty: 40,
+        }
+    }
+}
+
+impl EvalParams {
+    pub fn tunables(&mut self) -> Vec<Tunable<'_>> {
+        let [pawn, bishop, knight, rook, queen, _] = &mut self.piece_values;
+        vec![
+            Tunable::int("PawnValue", pawn, 50, 150, 5),
+            Tunable::int("BishopValue", bishop, 250, 400, 10),
+            Tunable::int("KnightValue", knight, 250, 400, 10),
+            Tunable::int("RookValue", rook, 400, 650, 15),
+            Tunable::int("QueenValue", queen, 750, 1100, 25),
+            Tunable::int("DoublePawnPenalty", &mut self.double_pawn_penalty, 0, 80, 4),
+        ]
+    }
+}
+
+pub enum TunableValue<'a> {
+    Int(&'a mut i32),
+    Depth(&'a mut usize),
+}
+
+// A parameter with the range and step size a tuner should use for it
+pub struct Tunable<'a> {
+    pub name: &'static str,
+    pub value: TunableValue<'a>,
+    pub min: i32,
+    pub max: i32,
+    pub step: i32,
+}
+
+impl<'a> Tunable<'a> {
+    fn int(name: &'static str, value: &'a mut i32, min: i32, max: i32, step: i32) -> Self {
+        let value = TunableValue::Int(value);
+        Tunable { name, value, min, max, step }
+    }
+
+    fn depth(name: &'static str, value: &'a mut usize, min: i32, max: i32, step: i32) -> Self {
+        let value = TunableValue::Depth(value);
+        Tunable { name, value, min, max, step }
+    }
+
+    pub fn get(&self) -> i32 {
+        match &self.value {
+            TunableValue::Int(value) => **value,
+            TunableValue::Depth(value) => **value as i32,
+        }
+    }
+
+    // Values outside the range are refused
+    pub fn set(&mut self, value: i32) -> bool {
+        if !(self.min..=self.max).contains(&value) {
+            return false;
+        }
+        match &mut self.value {
+            TunableValue::Int(field) => **field = value,
+            TunableValue::Depth(field) => **field = value as usize,
+        }
+        true
+    }
+
+    // Line of an OpenBench SPSA config, name, type, value, min, max, step and learning rate
+    pub fn spsa_line(&self) -> String {
+        format!(
+            "{}, int, {}, {}, {}, {}, 0.002",
+            self.name,
+            self.get(),
+            self.min,
+            self.max,
+            self.step
+        )
+    }
+}
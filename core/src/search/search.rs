@@ -1,23 +1,37 @@
 use std::{
-    i32,
+    mem,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+    thread,
     time::{Duration, Instant},
-    usize,
 };
 
 use crate::{
     board::{
-        board::Board,
-        defs::{Pieces, Sides},
+        board::{Board, ZobristRandoms},
+        cuckoo::Cuckoo,
+        defs::{Bitboard, Pieces, Side, Sides, ZobristHash, FILE_A, FILE_H, LIGHT_SQUARES},
     },
     movegen::{
         movegen::{bitscan_forward, MoveGen},
+        movelist::MoveList,
         moves::Move,
     },
 };
 
 use super::{
-    defs::{PieceTables, INF},
-    sorting::{retain_captures, sort_moves},
+    defs::{
+        EvalBreakdown, InfoCallback, PieceTables, ScoreBound, SearchInfo, INF,
+        MAX_DEPTH, SCALE_NORMAL,
+    },
+    heuristics::Heuristics,
+    see::see_ge,
+    movepicker::MovePicker,
+    params::{EvalParams, SearchParams, Tunable, INTERNAL_DEEPENING, INTERNAL_REDUCTIONS},
+    stack::SearchStack,
+    sorting::sort_moves,
     ttable::{MoveType, TranspositionEntry, TranspositionTable},
 };
 
@@ -34,18 +48,149 @@ pub const FLIP: [usize; 64] = [
 ];
 
 pub struct Search {
-    pub transposition_table: TranspositionTable,
+    // Shared with the helper threads
+    pub transposition_table: Arc<TranspositionTable>,
     pub best_move: Option<Move>,
     pub psqt_cache: Box<[[[i32; 64]; 6]; 257]>,
+    // Set from another thread to abort the current search
+    pub stop: Arc<AtomicBool>,
+    pub nodes: u64,
+    // Nodes of all threads in the running search, each adds its own every few nodes
+    shared_nodes: Arc<AtomicU64>,
+    // Part of `nodes` already added to `shared_nodes`
+    published_nodes: u64,
+    // Set once the time is up or `stop` is raised, the rest of the search unwinds without
+    // trusting any score
+    pub stopped: bool,
+    start_time: Instant,
+    duration: Duration,
+    // Score of the last completed iteration, the center of the next aspiration window
+    pub score: Option<i32>,
+    pub on_info: Option<InfoCallback>,
+    pub heuristics: Heuristics,
+    pub params: SearchParams,
+    pub eval_params: EvalParams,
+    // Per ply state of the line being searched
+    pub stack: SearchStack,
+    // Null moves are left out before this ply while verifying a null move cutoff
+    null_min_ply: usize,
+    // Base late move reductions by depth and move count, built from the LMR parameters
+    lmr_table: Box<[[usize; 64]; MAX_DEPTH]>,
+    // Reversible moves by key difference, to spot a repetition one move ahead
+    cuckoo: Cuckoo,
+    // Depth of the last completed iteration
+    pub completed_depth: usize,
+    // Lazy SMP, extra searches of the same position run on their own threads and only talk to
+    // this one through the transposition table
+    helpers: Vec<Search>,
 }
 impl Search {
-    // Refactor into a factory constructor! (cache shouldnt be public too)
+    // Transposition table size in megabytes
+    pub fn new(hash_mb: usize) -> Search {
+        Search::with_table(Arc::new(TranspositionTable::new(hash_mb)))
+    }
+
+    fn with_table(transposition_table: Arc<TranspositionTable>) -> Search {
+        let mut search = Search {
+            transposition_table,
+            best_move: None,
+            psqt_cache: Box::new([[[0; 64]; 6]; 257]),
+            stop: Arc::new(AtomicBool::new(false)),
+            nodes: 0,
+            shared_nodes: Arc::new(AtomicU64::new(0)),
+            published_nodes: 0,
+            stopped: false,
+            start_time: Instant::now(),
+            duration: Duration::MAX,
+            score: None,
+            on_info: None,
+            heuristics: Heuristics::new(),
+            params: SearchParams::default(),
+            eval_params: EvalParams::default(),
+            stack: SearchStack::new(),
+            null_min_ply: 0,
+            lmr_table: Box::new([[0; 64]; MAX_DEPTH]),
+            cuckoo: Cuckoo::new(&ZobristRandoms::new()),
+            completed_depth: 0,
+            helpers: Vec::new(),
+        };
+        search.init_psqt_cache();
+        search.init_lmr_table();
+        search
+    }
+
+    // Forgets everything learned from previous searches, so results don't depend on them
+    pub fn clear(&mut self) {
+        self.transposition_table.clear();
+        self.best_move = None;
+        self.nodes = 0;
+        self.score = None;
+        self.completed_depth = 0;
+        self.heuristics.clear();
+        self.stack.clear();
+        for helper in &mut self.helpers {
+            helper.clear();
+        }
+    }
+
+    // Total number of search threads, this one included
+    pub fn threads(&self) -> usize {
+        self.helpers.len() + 1
+    }
+
+    pub fn set_threads(&mut self, threads: usize) {
+        let helpers = threads.max(1) - 1;
+        self.helpers.truncate(helpers);
+        while self.helpers.len() < helpers {
+            let mut helper = Search::with_table(Arc::clone(&self.transposition_table));
+            helper.params = self.params.clone();
+            helper.eval_params = self.eval_params.clone();
+            self.helpers.push(helper);
+        }
+    }
+
+    // Replaces the transposition table of all threads with an empty one
+    pub fn set_table_size(&mut self, hash_mb: usize) {
+        self.transposition_table = Arc::new(TranspositionTable::new(hash_mb));
+        for helper in &mut self.helpers {
+            helper.transposition_table = Arc::clone(&self.transposition_table);
+        }
+    }
+
+    // Nodes searched by all threads in the last search
+    pub fn total_nodes(&self) -> u64 {
+        self.nodes + self.helpers.iter().map(|helper| helper.nodes).sum::<u64>()
+    }
+
+    // Search and evaluation parameters a tuner can change, see SearchParams::tunables
+    pub fn tunables(&mut self) -> Vec<Tunable<'_>> {
+        let mut tunables = self.params.tunables();
+        tunables.extend(self.eval_params.tunables());
+        tunables
+    }
+
     pub fn init_psqt_cache(&mut self) {
         for weight in 0..257 {
             self.psqt_cache[weight] = self.gen_psqt_set(weight as i32);
         }
     }
 
+    pub fn init_lmr_table(&mut self) {
+        let base = self.params.lmr_base as f64 / 100.0;
+        let factor = self.params.lmr_factor as f64 / 100.0;
+        for depth in 1..MAX_DEPTH {
+            for move_count in 1..64 {
+                let scale = (depth as f64).ln() * (move_count as f64).ln();
+                self.lmr_table[depth][move_count] = (base + scale * factor).max(0.0) as usize;
+            }
+        }
+    }
+
+    // Base reduction of a late quiet move, before the adjustments for the node and move
+    pub fn reduction(&self, depth: usize, move_count: usize) -> usize {
+        self.lmr_table[depth.min(MAX_DEPTH - 1)][move_count.min(63)]
+    }
+
     pub fn find_best_move_iter(
         &mut self,
         board: &mut Board,
@@ -53,26 +198,117 @@ impl Search {
         max_depth: usize,
         duration: Duration,
     ) -> Option<Move> {
-        let mut moves = mg.gen_legal_moves_no_rep(board);
-        if moves.index == 0 {
-            return None;
+        self.transposition_table.new_search();
+        self.shared_nodes.store(0, Ordering::Relaxed);
+        if self.helpers.is_empty() {
+            return self.iterative_deepening(board, mg, max_depth, duration, 0);
         }
 
-        let mut best_move = moves.moves[0].clone();
-        let start_time = Instant::now();
+        // Helpers run until this thread is done, whatever stopped it
+        let done = Arc::new(AtomicBool::new(false));
+        let mut helpers = mem::take(&mut self.helpers);
+        let best_move = thread::scope(|scope| {
+            for (i, helper) in helpers.iter_mut().enumerate() {
+                let mut board = board.clone();
+                helper.stop = Arc::clone(&done);
+                helper.shared_nodes = Arc::clone(&self.shared_nodes);
+                helper.params = self.params.clone();
+                helper.eval_params = self.eval_params.clone();
+                // Half the helpers start a depth ahead, so threads don't all search the same tree
+                scope.spawn(move || {
+                    helper.iterative_deepening(&mut board, mg, max_depth, Duration::MAX, (i + 1) % 2)
+                });
+            }
 
-        // Incase search can't even reach 1 depth (wtf)
-        sort_moves(&mut moves, None);
-        self.best_move = Some(moves.moves[0].clone());
-        for depth in 1..max_depth {
-            let current_best = self.find_best_move(board, mg, depth, start_time, duration);
+            let best_move = self.iterative_deepening(board, mg, max_depth, duration, 0);
+            done.store(true, Ordering::Relaxed);
+            best_move
+        });
+        self.helpers = helpers;
 
-            if start_time.elapsed() > duration {
-                break;
+        best_move.map(|best_move| self.vote(best_move))
+    }
+
+    // Picks the move the threads agree on most, trusting deeper and better scored searches more.
+    // Only threads that completed a root iteration of their own have a move and score to offer,
+    // weighted by the depth they actually searched.
+    fn vote(&self, best_move: Move) -> Move {
+        let threads = || std::iter::once(self as &Search).chain(self.helpers.iter());
+        let candidates = || {
+            threads()
+                .filter(|search| search.completed_depth > 0)
+                .filter_map(|search| {
+                    Some((search.best_move?, search.score?, search.completed_depth as i64))
+                })
+        };
+        let Some(min_score) = candidates().map(|(_, score, _)| score).min() else {
+            return best_move;
+        };
+
+        let mut votes: Vec<(Move, i64)> = Vec::new();
+        for (mv, score, depth) in candidates() {
+            let weight = (score as i64 - min_score as i64 + 14) * depth;
+            match votes.iter_mut().find(|(voted, _)| *voted == mv) {
+                Some((_, total)) => *total += weight,
+                None => votes.push((mv, weight)),
             }
+        }
+
+        // Ties go to this thread's move, which comes first
+        let mut best = (best_move, i64::MIN);
+        for (mv, total) in votes {
+            if total > best.1 {
+                best = (mv, total);
+            }
+        }
+        best.0
+    }
+
+    fn iterative_deepening(
+        &mut self,
+        board: &mut Board,
+        mg: &MoveGen,
+        max_depth: usize,
+        duration: Duration,
+        skip: usize,
+    ) -> Option<Move> {
+        let mut moves = mg.gen_legal_moves(board);
+        if moves.index == 0 {
+            return None;
+        }
+
+        let mut best_move = moves.moves[0];
+        self.start_time = Instant::now();
+        self.duration = duration;
+        self.stopped = false;
+        self.nodes = 0;
+        self.published_nodes = 0;
+        self.completed_depth = 0;
+        // Killers only make sense for the positions of one search
+        self.stack.clear();
+        // The parameters may have changed since the last search
+        self.init_lmr_table();
+
+        // Until the root searches a move, the table's move for this position if it has a legal
+        // one, and the first move otherwise in case not even depth 1 completes
+        sort_moves(&mut moves, None);
+        let hash_move = self
+            .transposition_table
+            .get(board.zobrist_hash())
+            .and_then(|entry| entry.best_move(board))
+            .filter(|mv| moves.iter().any(|legal| legal == mv));
+        self.best_move = hash_move.or(Some(moves.moves[0]));
+        self.score = None;
+        for depth in (1 + skip).min(max_depth)..=max_depth {
+            // Even when stopped this is the previous iteration's move, or one proven better
+            let current_best = self.find_best_move(board, mg, depth);
 
             // We already established it's not empty
             best_move = current_best.unwrap();
+
+            if self.should_stop() {
+                break;
+            }
         }
 
         Some(best_move)
@@ -83,307 +319,699 @@ impl Search {
         board: &mut Board,
         mg: &MoveGen,
         depth: usize,
-        start_time: Instant,
-        duration: Duration,
     ) -> Option<Move> {
-        let moves = mg.gen_legal_moves_no_rep(board);
+        let moves = mg.gen_legal_moves(board);
 
         if moves.index == 0 {
             return None;
         }
 
-        let score = self.negascout(
-            board,
-            mg,
-            start_time,
-            duration,
-            -INF,
-            INF,
-            depth,
-            0,
-        );
+        // Aspiration window around the last score, unless that was a mate
+        let mut delta = self.params.aspiration_window;
+        let (mut alpha, mut beta) = match self.score {
+            Some(score) if depth >= 4 && score.abs() < INF - MAX_DEPTH as i32 * 2 => (
+                score.saturating_sub(delta).max(-INF),
+                score.saturating_add(delta).min(INF),
+            ),
+            _ => (-INF, INF),
+        };
+
+        // The root only replaces the best move with one that completed a search above alpha,
+        // so after a fail low or an abort it still holds the best move proven so far
+        loop {
+            let score = self.negascout(board, mg, alpha, beta, depth, 0);
+
+            if self.stopped {
+                break;
+            }
+
+            let bound = if score <= alpha {
+                beta = (alpha + beta) / 2;
+                alpha = score.saturating_sub(delta).max(-INF);
+                ScoreBound::Upper
+            } else if score >= beta {
+                beta = score.saturating_add(delta).min(INF);
+                ScoreBound::Lower
+            } else {
+                self.score = Some(score);
+                self.completed_depth = depth;
+                ScoreBound::Exact
+            };
+
+            let info = SearchInfo {
+                depth,
+                score,
+                bound,
+                nodes: self.searched_nodes(),
+                hashfull: self.transposition_table.hashfull(),
+                time: self.start_time.elapsed(),
+                pv: self.principal_variation(board, mg, depth),
+            };
+            if let Some(on_info) = self.on_info.as_mut() {
+                on_info(&info);
+            }
+
+            if bound == ScoreBound::Exact {
+                break;
+            }
+            delta = delta.saturating_mul(2);
+        }
+
+        self.best_move
+    }
+
+    // Once this returns true the search unwinds and stays stopped until the next one starts
+    pub fn should_stop(&mut self) -> bool {
+        self.publish_nodes();
+        if !self.stopped
+            && (self.stop.load(Ordering::Relaxed) || self.start_time.elapsed() > self.duration)
+        {
+            self.stopped = true;
+        }
+        self.stopped
+    }
+
+    fn publish_nodes(&mut self) {
+        let unpublished = self.nodes - self.published_nodes;
+        self.shared_nodes.fetch_add(unpublished, Ordering::Relaxed);
+        self.published_nodes = self.nodes;
+    }
+
+    // Nodes of all threads so far in the running search, helpers lag by up to a batch each
+    fn searched_nodes(&self) -> u64 {
+        self.shared_nodes.load(Ordering::Relaxed) + self.nodes - self.published_nodes
+    }
+
+    // Follows hash moves from the root, starting with the current best move
+    pub fn principal_variation(
+        &self,
+        board: &mut Board,
+        mg: &MoveGen,
+        max_length: usize,
+    ) -> Vec<Move> {
+        let mut pv = Vec::new();
+        let mut next = self.best_move;
 
-        if start_time.elapsed() < duration {
-            println!("info score cp {} depth {}", score, depth);
+        while let Some(mv) = next {
+            if pv.len() >= max_length || !mg.gen_legal_moves(board).iter().any(|m| *m == mv) {
+                break;
+            }
+
+            board.do_move(&mv);
+            pv.push(mv);
+            next = self
+                .transposition_table
+                .get(board.zobrist_hash())
+                .and_then(|entry| entry.best_move(board));
         }
-        return self.best_move.clone();
+
+        for mv in pv.iter().rev() {
+            board.undo_move(mv);
+        }
+
+        pv
     }
 
     pub fn negascout(
         &mut self,
         board: &mut Board,
         mg: &MoveGen,
-        start_time: Instant,
-        duration: Duration,
         mut alpha: i32,
         beta: i32,
         mut depth: usize,
         ply: usize,
     ) -> i32 {
-        if start_time.elapsed() > duration {
+        // The score of an aborted node is meaningless, callers check `stopped` before using it.
+        // The clock is only read every few nodes.
+        if self.stopped || (self.nodes & 1023 == 0 && self.should_stop()) {
             return 0;
         }
 
-        if depth == 0 {
-            if mg.in_check(board, board.us()) && ply < 10 {
-                depth += 1
-            } else {
-                return self.quiesce(board, mg, alpha, beta, 10);
+        // The root always gets searched, a move is wanted even in a drawn position
+        if ply > 0 && self.is_draw(board, mg, ply) {
+            return 0;
+        }
+        // A move back into an earlier position is there to take, so we can't do worse than a draw
+        if ply > 0 && alpha < 0 && board.has_game_cycle(ply, &self.cuckoo) {
+            alpha = 0;
+            if alpha >= beta {
+                return alpha;
             }
         }
+        let hash = board.zobrist_hash();
 
-        // Check for draw or checkmate
-        let mut moves = mg.gen_legal_moves_no_rep(board);
-        if moves.index == 0 {
-            if mg.in_check(board, board.us()) {
-                return -INF + (ply as i32);
-            }
-            return 0;
+        if depth == 0 || ply >= MAX_DEPTH - 1 {
+            return self.quiesce(board, mg, alpha, beta, ply);
         }
+        self.nodes += 1;
 
         // Probe transposition table for principal move, or an existing evaluation
-        let hash = board.zobrist_hash();
-        let tt_entry = self.transposition_table.get(hash);
-        let hash_move: Option<Move> = tt_entry.as_ref().map(|entry| entry.best_move.clone());
-
-        let estimation = match tt_entry {
-            // If depth is better try to prune instantly
-            Some(entry) if entry.depth >= depth => match entry.move_type {
+        let tt_entry = self.probe(hash, ply);
+        let mut hash_move = tt_entry.as_ref().and_then(|entry| entry.best_move(board));
+        // Set while searching the other moves for a singular extension, the table's score
+        // includes the left out move so it can't be trusted
+        let excluded = self.stack[ply].excluded;
+
+        // If depth is better try to prune instantly. Never at the root, which has to search a
+        // move to report.
+        if let Some(entry) = tt_entry
+            .as_ref()
+            .filter(|entry| ply > 0 && entry.depth >= depth && excluded.is_none())
+        {
+            match entry.move_type {
                 MoveType::Exact => return entry.eval,
                 MoveType::Minimum if entry.eval >= beta => return entry.eval,
                 MoveType::Maximum if entry.eval <= alpha => return entry.eval,
-                _ => entry.eval,
+                _ => (),
+            }
+        }
+
+        // Static eval cached in the table, improved by the stored score when its bound allows
+        let static_eval = match &tt_entry {
+            Some(entry) => entry.static_eval,
+            None => self.static_eval(board),
+        };
+        let estimation = match &tt_entry {
+            Some(entry) => match entry.move_type {
+                MoveType::Exact => entry.eval,
+                MoveType::Minimum => entry.eval.max(static_eval),
+                MoveType::Maximum => entry.eval.min(static_eval),
             },
-            // Otherwise just pick static eval
-            _ => self.static_eval(board),
+            None => static_eval,
         };
 
         let in_check = mg.in_check(board, board.us());
+        self.stack[ply].static_eval = static_eval;
+        self.stack[ply].in_check = in_check;
+        // The position got better since our last move, so a fail low is less likely
+        let improving = self.stack.improving(ply);
+        let pv_node = alpha != beta - 1;
+
         // Reverse futility pruning
-        if depth >= 3 && beta.abs() < 1000000 && !in_check {
-            let margin: i32 = 150 * (depth as i32);
+        if ply > 0
+            && depth >= 3
+            && beta.abs() < INF - MAX_DEPTH as i32 * 2
+            && !in_check
+            && excluded.is_none()
+        {
+            let margin: i32 = self.params.rfp_margin * (depth - improving as usize) as i32;
 
             if estimation >= beta + margin {
                 return estimation;
             }
         }
 
-        // Null move pruning
-        // Check if we are in check/only have king/pawns
-        let r = 3;
+        // Razoring, at low depth a position hopelessly below alpha only gets a quiescence search
+        let razor_margin = self.params.razor_base + self.params.razor_margin * depth as i32;
+        if depth <= self.params.razor_max_depth
+            && alpha == beta - 1
+            && !in_check
+            && excluded.is_none()
+            && alpha.abs() < INF - MAX_DEPTH as i32 * 2
+            && estimation + razor_margin < alpha
+        {
+            let score = self.quiesce(board, mg, alpha - 1, alpha, ply);
+            if score < alpha {
+                return score;
+            }
+        }
+
+        // Null move pruning, if passing the turn still fails high a real move will too. Not twice
+        // in a row, nor with only pawns left where zugzwang is common.
+        let pieces = board.bb_side[board.us()]
+            & !(board.bb_pieces[board.us()][Pieces::KING]
+                | board.bb_pieces[board.us()][Pieces::PAWN]);
         if !in_check
-        // Try - 50
             && estimation >= beta
             && depth >= 3
-            && ply > 0
-            && board.game_state.can_nullmove
+            && ply >= self.null_min_ply
+            && self.stack.previous_move(ply).is_some()
+            && excluded.is_none()
             && alpha == beta - 1
-            && board.bb_side[board.us()]
-                != board.bb_pieces[board.us()][Pieces::KING]
-                    | board.bb_pieces[board.us()][Pieces::PAWN]
+            && pieces != 0
         {
-            let old_state = board.game_state.clone();
-
-            board.game_state.active_color ^= 1;
-            board.game_state.enpassant_piece = None;
-            board.game_state.can_nullmove = false;
-
-            let v = -self.negascout(
-                board,
-                mg,
-                start_time,
-                duration,
-                -beta,
-                -(beta - 1),
-                depth - r,
-                ply + 1,
-            );
-
-            board.game_state = old_state;
-
-            if v >= beta {
-                let verify_score = -self.negascout(
-                    board,
-                    mg,
-                    start_time,
-                    duration,
-                    -beta,
-                    -(beta - 1),
-                    depth - r + 1,
-                    ply + 1,
-                );
+            // Deeper and further above beta, the more the null move search can be reduced
+            let eval_margin = (estimation - beta) / self.params.null_move_eval_divisor;
+            let r = self.params.null_move_reduction
+                + depth / self.params.null_move_depth_divisor
+                + eval_margin.min(3) as usize;
+            let null_depth = depth.saturating_sub(r);
+
+            board.do_null_move();
+            self.stack[ply].current_move = None;
+            let score = -self.negascout(board, mg, -beta, -(beta - 1), null_depth, ply + 1);
+            board.undo_null_move();
+            if self.stopped {
+                return 0;
+            }
+
+            if score >= beta {
+                // An unproven mate from a null move isn't worth returning
+                let score = if score >= INF - MAX_DEPTH as i32 * 2 { beta } else { score };
+
+                // Deep nodes and a single piece left are where zugzwang could fool it, there
+                // the same node is searched again without null moves for the next plies
+                let verify = depth >= self.params.null_verify_depth || pieces.count_ones() == 1;
+                if !verify || self.null_min_ply > 0 {
+                    return score;
+                }
+                self.null_min_ply = ply + 1 + 3 * null_depth / 4;
+                let verify_score = self.negascout(board, mg, beta - 1, beta, null_depth, ply);
+                self.null_min_ply = 0;
+                if self.stopped {
+                    return 0;
+                }
                 if verify_score >= beta {
-                    return verify_score;
+                    return score;
                 }
             }
         }
 
-        sort_moves(&mut moves, hash_move.clone());
+        // Without a hash move the ordering is poor, either search less deep here and rely on the
+        // next iteration, or find one with a shallower search first
+        if hash_move.is_none() && excluded.is_none() {
+            match self.params.internal_iteration {
+                INTERNAL_REDUCTIONS if depth >= self.params.iir_min_depth => depth -= 1,
+                INTERNAL_DEEPENING if pv_node && depth >= self.params.iid_min_depth => {
+                    self.negascout(board, mg, alpha, beta, depth - 2, ply);
+                    if self.stopped {
+                        return 0;
+                    }
+                    hash_move = self
+                        .transposition_table
+                        .get(hash)
+                        .and_then(|entry| entry.best_move(board));
+                }
+                _ => (),
+            }
+        }
+
+        let us = board.us();
+        // Moves one and two plies back
+        let previous = self.stack.continuations(ply);
+        let killers = self.stack[ply].killers;
+        let piece_values = self.eval_params.piece_values;
+        let heuristics = &self.heuristics;
+        let mut picker =
+            MovePicker::new(board, mg, hash_move, heuristics, killers, previous, piece_values);
         let mut best_score = -INF;
-        let mut best_move = moves.moves[0].clone();
+        let mut best_move = None;
+        // Moves that didn't cause a cutoff, penalized if a later one does
+        let mut quiets = MoveList::new();
+        let mut captures = MoveList::new();
+        let mut move_count = 0;
+        // The bound stored in the table is relative to the window the node was searched with
+        let original_alpha = alpha;
+
+        while let Some(mv) = picker.next(board, mg, &self.heuristics) {
+            if Some(mv) == excluded {
+                continue;
+            }
+            let is_quiet = !mv.is_tactical();
+            // Moves may only be pruned in non-PV nodes, once one has kept us from being mated
+            let can_prune = move_count > 0
+                && !in_check
+                && alpha == beta - 1
+                && best_score > -INF + MAX_DEPTH as i32 * 2;
+
+            // Late move pruning, at low depth the quiets ordered last are rarely any good. Twice
+            // as many are searched when the position is improving.
+            if can_prune
+                && is_quiet
+                && depth <= self.params.lmp_max_depth
+                && move_count >= (self.params.lmp_base + depth * depth) / (2 - improving as usize)
+            {
+                continue;
+            }
 
-        let original_can_nullmove = board.game_state.can_nullmove;
+            // Futility pruning, a quiet move close to the horizon won't make up for a static
+            // eval far below alpha
+            let futility_margin =
+                self.params.futility_base + self.params.futility_margin * depth as i32;
+            if can_prune
+                && is_quiet
+                && depth <= self.params.futility_max_depth
+                && estimation + futility_margin <= alpha
+            {
+                continue;
+            }
+
+            // History pruning, quiets that keep failing elsewhere
+            if can_prune
+                && is_quiet
+                && depth <= self.params.history_prune_max_depth
+                && self.heuristics.quiet_history(us, mv, previous)
+                    < -self.params.history_prune_margin * depth as i32
+            {
+                continue;
+            }
+
+            // SEE pruning, skip moves that lose too much material in a non-PV node. Promotions
+            // are always searched, SEE only sees the new piece being traded off.
+            if can_prune && !mv.is_promotion() && depth <= self.params.see_prune_max_depth {
+                let threshold = if is_quiet {
+                    -self.params.see_quiet_margin * (depth * depth) as i32
+                } else {
+                    -self.params.see_capture_margin * depth as i32
+                };
+                if !see_ge(mg, board, mv, threshold, &piece_values) {
+                    continue;
+                }
+            }
+
+            let can_extend = self.stack[ply].extensions < self.params.extension_budget;
+            let mut extension = 0;
+
+            // Singular extension, when no other move gets near the hash move's score it is the
+            // only good one and worth a closer look
+            if can_extend
+                && self.params.singular_extension
+                && ply > 0
+                && depth >= self.params.singular_min_depth
+                && Some(mv) == hash_move
+                && excluded.is_none()
+            {
+                if let Some(entry) = tt_entry.as_ref().filter(|entry| {
+                    entry.depth + 3 >= depth
+                        && entry.move_type != MoveType::Maximum
+                        && entry.eval.abs() < INF - MAX_DEPTH as i32 * 2
+                }) {
+                    let singular_beta = entry.eval - self.params.singular_margin * depth as i32;
+                    self.stack[ply].excluded = Some(mv);
+                    let score = self.negascout(
+                        board,
+                        mg,
+                        singular_beta - 1,
+                        singular_beta,
+                        (depth - 1) / 2,
+                        ply,
+                    );
+                    self.stack[ply].excluded = None;
+                    if self.stopped {
+                        return 0;
+                    }
+                    if score < singular_beta {
+                        extension = 1;
+                    }
+                }
+            }
 
-        let mut move_count = 0;
-        for mv in moves.iter() {
             board.do_move(&mv);
-            board.game_state.can_nullmove = true;
-
-            // Late move reduction
-            let can_reduce = 
-                depth >= 3 &&
-                move_count >= 3 &&
-                !in_check &&
-                mv.capture().is_none() &&
-                !mv.is_promotion() &&
-                alpha > -INF &&
-                beta < INF;
-            
-            // Magic LMR Formula
-            let mut reduction = if can_reduce {
-                pub const CONSTANT: f64 = 2.78;
-                pub const FACTOR: f64 = 0.40;
-                (CONSTANT + (depth.min(32) as f64).ln() * (move_count.min(32) as f64).ln() * FACTOR) as usize
-            } else {
-                0
-            };
+            if mg.in_check(board, us) {
+                board.undo_move(&mv);
+                continue;
+            }
+            self.transposition_table.prefetch(board.zobrist_hash());
+
+            let gives_check = mg.in_check(board, board.us());
+            if can_extend && extension == 0 {
+                let gives_check = self.params.check_extension && gives_check;
+                // Recaptures only in PV nodes, they are too common to extend everywhere
+                let recapture = self.params.recapture_extension
+                    && alpha != beta - 1
+                    && mv.capture().is_some()
+                    && previous[0]
+                        .is_some_and(|prev| prev.capture().is_some() && prev.to() == mv.to());
+                // The seventh rank from the mover's side, squares 8..16 for white
+                let pawn_push = self.params.pawn_push_extension
+                    && mv.piece() == Pieces::PAWN
+                    && !mv.is_promotion()
+                    && mv.to() / 8 == if us == Sides::WHITE { 1 } else { 6 };
+                if gives_check || recapture || pawn_push {
+                    extension = 1;
+                }
+            }
+            self.stack[ply + 1].extensions = self.stack[ply].extensions + extension;
+            let new_depth = depth - 1 + extension;
+            self.stack[ply].current_move = Some(mv);
+
+            // Late move reduction, less in PV nodes and for moves that look promising
+            let mut reduction = 0;
+            if depth >= 3 && move_count >= 3 && !in_check && is_quiet {
+                let mut r = self.reduction(depth, move_count) as i32;
+                if pv_node {
+                    r -= 1;
+                }
+                if !improving {
+                    r += 1;
+                }
+                if self.stack.is_killer(ply, mv).is_some() {
+                    r -= 1;
+                }
+                if gives_check {
+                    r -= 1;
+                }
+                let history = self.heuristics.quiet_history(us, mv, previous);
+                r -= history / self.params.lmr_history_divisor;
+                // Never more than new_depth, otherwise the subtraction below overflows
+                reduction = (r.max(0) as usize).min(new_depth);
+            }
 
-            // Make it not be larger than depth - 1 (otherwise it tries to subtract with overflow)
-            reduction = reduction.min(depth - 1);
-            
             let mut score;
             if move_count == 0 {
-                score = -self.negascout(
-                    board,
-                    mg,
-                    start_time,
-                    duration,
-                    -beta,
-                    -alpha,
-                    depth - 1,
-                    ply + 1,
-                );
+                score = -self.negascout(board, mg, -beta, -alpha, new_depth, ply + 1);
             } else {
-                // Null window search
+                // Reduced null window search
                 score = -self.negascout(
                     board,
                     mg,
-                    start_time,
-                    duration,
                     -(alpha + 1),
                     -alpha,
-                    depth - 1 - reduction,
+                    new_depth - reduction,
                     ply + 1,
                 );
 
-                // Search entire window if null window failed
-                if score > alpha {
-                    score = -self.negascout(
-                        board,
-                        mg,
-                        start_time,
-                        duration,
-                        -beta,
-                        -alpha,
-                        depth - 1,
-                        ply + 1,
-                    );
+                // The reduction was wrong, try again at full depth
+                if score > alpha && reduction > 0 {
+                    score = -self.negascout(board, mg, -(alpha + 1), -alpha, new_depth, ply + 1);
+                }
+
+                // Only PV nodes need the exact score
+                if score > alpha && score < beta && pv_node {
+                    score = -self.negascout(board, mg, -beta, -alpha, new_depth, ply + 1);
                 }
             }
 
             board.undo_move(&mv);
-            board.game_state.can_nullmove = original_can_nullmove;
+            move_count += 1;
+
+            // Nothing from an aborted node reaches the table or the root move
+            if self.stopped {
+                return 0;
+            }
 
             if score > best_score {
                 best_score = score;
-                best_move = mv.clone();
-                if ply == 0 {
-                    self.best_move = Some(*mv);
-                }
+                best_move = Some(mv);
 
                 if score > alpha {
+                    // Only a move that beat alpha is known to be the best so far
+                    if ply == 0 {
+                        self.best_move = Some(mv);
+                    }
                     alpha = score;
                     if alpha >= beta {
+                        if is_quiet {
+                            let failed = &quiets.moves[..quiets.index];
+                            self.stack.update_killers(ply, mv);
+                            self.heuristics.update_cutoff(us, depth, mv, previous, failed);
+                        }
+                        let failed = &captures.moves[..captures.index];
+                        let capture = (!is_quiet).then_some(mv);
+                        self.heuristics.update_captures(depth, capture, failed);
                         break;
                     }
                 }
             }
 
-            move_count += 1;
-        }
-        self.transposition_table.insert(TranspositionEntry {
-            depth,
-            key: hash,
-            best_move: best_move.clone(),
-            eval: best_score,
-            move_type: if best_score <= alpha {
-                MoveType::Maximum
-            } else if best_score >= beta {
-                MoveType::Minimum
+            if is_quiet {
+                quiets.push(mv);
             } else {
-                MoveType::Exact
-            },
-        });
+                captures.push(mv);
+            }
+        }
 
-        if ply == 0 {
-            self.best_move = Some(best_move);
+        let Some(best_move) = best_move else {
+            // Only the excluded move, which is then as singular as it gets
+            if excluded.is_some() {
+                return alpha;
+            }
+            if in_check {
+                return -INF + (ply as i32);
+            }
+            return 0;
+        };
+
+        let move_type = if best_score <= original_alpha {
+            MoveType::Maximum
+        } else if best_score >= beta {
+            MoveType::Minimum
+        } else {
+            MoveType::Exact
+        };
+        if excluded.is_some() {
+            return best_score;
         }
+        self.transposition_table.insert(TranspositionEntry::new(
+            hash,
+            Some(best_move),
+            score_to_tt(best_score, ply),
+            static_eval,
+            depth,
+            move_type,
+        ));
 
         best_score
     }
 
+    // Repetitions and the fifty-move rule. Checkmate and stalemate are left to the move loop,
+    // except when they decide whether the fifty-move rule applies.
+    fn is_draw(&self, board: &mut Board, mg: &MoveGen, ply: usize) -> bool {
+        if board.is_repetition(ply) || board.is_insufficient_material() {
+            return true;
+        }
+        board.is_fifty_move_draw()
+            && !(mg.in_check(board, board.us()) && mg.gen_legal_moves(board).index == 0)
+    }
+
+    // Table entry with its mate score made relative to the root again
+    fn probe(&self, hash: ZobristHash, ply: usize) -> Option<TranspositionEntry> {
+        let mut entry = self.transposition_table.get(hash)?;
+        entry.eval = score_from_tt(entry.eval, ply);
+        Some(entry)
+    }
+
+    // Searches captures until the position is quiet, or every evasion when in check
     pub fn quiesce(
         &mut self,
         board: &mut Board,
         mg: &MoveGen,
         mut alpha: i32,
         beta: i32,
-        depth: usize,
+        ply: usize,
     ) -> i32 {
-        let stand_pat = self.static_eval(board);
-        if stand_pat >= beta || depth == 0 {
-            return stand_pat;
+        if self.stopped || (self.nodes & 1023 == 0 && self.should_stop()) {
+            return 0;
         }
-        if stand_pat > alpha {
-            alpha = stand_pat;
+        self.nodes += 1;
+
+        let hash = board.zobrist_hash();
+        let in_check = mg.in_check(board, board.us());
+        let tt_entry = self.probe(hash, ply);
+        // Out of plies even in check, a draw score would hide how the position stands
+        if ply >= MAX_DEPTH - 1 {
+            return self.static_eval(board);
         }
 
-        let moves = mg.gen_legal_moves_no_rep(board);
-        if moves.index == 0 {
-            if mg.in_check(board, Sides::WHITE) || mg.in_check(board, Sides::BLACK) {
-                return -INF;
+        // Any entry is deep enough here
+        if let Some(entry) = &tt_entry {
+            match entry.move_type {
+                MoveType::Exact => return entry.eval,
+                MoveType::Minimum if entry.eval >= beta => return entry.eval,
+                MoveType::Maximum if entry.eval <= alpha => return entry.eval,
+                _ => (),
             }
-            return 0;
         }
 
-        let mut captures = retain_captures(moves);
+        let static_eval = match &tt_entry {
+            Some(entry) => entry.static_eval,
+            None => self.static_eval(board),
+        };
 
-        if captures.index == 0 {
-            return stand_pat;
+        // When in check there is no standing pat, only the evasions count
+        let mut best_score = -INF + ply as i32;
+        if !in_check {
+            best_score = static_eval;
+            if static_eval >= beta {
+                self.transposition_table.insert(TranspositionEntry::new(
+                    hash,
+                    None,
+                    score_to_tt(static_eval, ply),
+                    static_eval,
+                    0,
+                    MoveType::Minimum,
+                ));
+                return static_eval;
+            }
+            alpha = alpha.max(static_eval);
         }
+        let original_alpha = alpha;
+
+        let us = board.us();
+        let hash_move = tt_entry.as_ref().and_then(|entry| entry.best_move(board));
+        let piece_values = self.eval_params.piece_values;
+        let mut picker = if in_check {
+            let previous = self.stack.continuations(ply);
+            let killers = self.stack[ply].killers;
+            let heuristics = &self.heuristics;
+            MovePicker::new(board, mg, hash_move, heuristics, killers, previous, piece_values)
+        } else {
+            MovePicker::new_quiescence(board, mg, hash_move, piece_values)
+        };
+        let mut best_move = None;
+
+        while let Some(mv) = picker.next(board, mg, &self.heuristics) {
+            // Delta pruning, even winning the piece for free won't get close to alpha
+            if !in_check {
+                let captured = if mv.is_enpassant() {
+                    Some(Pieces::PAWN)
+                } else {
+                    mv.capture()
+                };
+                let mut gain = captured.map_or(0, |piece| piece_values[piece]);
+                if mv.is_promotion() {
+                    gain += piece_values[mv.promotion_piece()] - piece_values[Pieces::PAWN];
+                }
+                if static_eval + gain + self.params.delta_margin <= alpha {
+                    continue;
+                }
+            }
 
-        // TODO: Check if we should grab hash move from tt
-        sort_moves(&mut captures, None);
-
-        let mut best_value = -INF;
-        for mv in captures.iter() {
             board.do_move(&mv);
-            let score = -self.quiesce(board, mg, -beta, -alpha, depth - 1);
+            if mg.in_check(board, us) {
+                board.undo_move(&mv);
+                continue;
+            }
+            self.transposition_table.prefetch(board.zobrist_hash());
+            self.stack[ply].current_move = Some(mv);
+
+            let score = -self.quiesce(board, mg, -beta, -alpha, ply + 1);
             board.undo_move(&mv);
 
-            if score > best_value {
-                best_value = score;
+            if self.stopped {
+                return 0;
+            }
+
+            if score > best_score {
+                best_score = score;
                 if score > alpha {
+                    best_move = Some(mv);
                     alpha = score;
+                    if alpha >= beta {
+                        break;
+                    }
                 }
             }
-
-            if score >= beta {
-                return best_value;
-            }
         }
 
-        best_value
+        // Mated if in check and nothing got us out of it
+        let move_type = if best_score >= beta {
+            MoveType::Minimum
+        } else if best_score > original_alpha {
+            MoveType::Exact
+        } else {
+            MoveType::Maximum
+        };
+        self.transposition_table.insert(TranspositionEntry::new(
+            hash,
+            best_move,
+            score_to_tt(best_score, ply),
+            static_eval,
+            0,
+            move_type,
+        ));
+
+        best_score
     }
 
     pub fn static_eval(&self, board: &mut Board) -> i32 {
@@ -397,6 +1025,8 @@ impl Search {
         eval += self.double_pawn_penalty(board);
         // Passed pawn bonus
 
+        eval = eval * self.endgame_scale(board, eval) / SCALE_NORMAL;
+
         let side2move = match board.us() {
             Sides::WHITE => 1,
             _ => -1,
@@ -404,7 +1034,120 @@ impl Search {
         eval * side2move
     }
 
-    // Remove 40 points for each file with more than one pawn
+    pub fn eval_breakdown(&self, board: &Board) -> EvalBreakdown {
+        let phase = self.get_phase(board);
+        let material = self.material(board);
+        let psqt = self.apply_psqt(board, self.get_psqt_set(phase)) - material;
+        let double_pawns = self.double_pawn_penalty(board);
+        let unscaled = material + psqt + double_pawns;
+        let scale = self.endgame_scale(board, unscaled);
+
+        EvalBreakdown {
+            material,
+            psqt,
+            double_pawns,
+            phase,
+            scale,
+            total: unscaled * scale / SCALE_NORMAL,
+        }
+    }
+
+    // Out of SCALE_NORMAL, how much of a white point of view `eval` the side ahead can hope to
+    // turn into a win, for endings known to be hard or impossible to win
+    pub fn endgame_scale(&self, board: &Board, eval: i32) -> i32 {
+        if board.is_insufficient_material() {
+            return 0;
+        }
+
+        let strong = if eval > 0 { Sides::WHITE } else { Sides::BLACK };
+        let weak = strong ^ 1;
+        let pieces = |side: Side, piece| board.bb_pieces[side][piece];
+        let non_pawn = |side: Side| {
+            board.bb_side[side] & !(pieces(side, Pieces::PAWN) | pieces(side, Pieces::KING))
+        };
+        let non_pawn_material = |side: Side| {
+            (Pieces::BISHOP..Pieces::KING)
+                .map(|piece| {
+                    pieces(side, piece).count_ones() as i32 * self.eval_params.piece_values[piece]
+                })
+                .sum::<i32>()
+        };
+        // The side has exactly one piece besides the king and pawns, of the given type
+        let only = |side: Side, piece| {
+            non_pawn(side).count_ones() == 1 && non_pawn(side) == pieces(side, piece)
+        };
+
+        // Without pawns, being up less than a rook rarely wins, and never with a lone minor
+        let strong_material = non_pawn_material(strong);
+        if pieces(strong, Pieces::PAWN) == 0
+            && strong_material - non_pawn_material(weak)
+                <= self.eval_params.piece_values[Pieces::BISHOP]
+        {
+            return if strong_material < self.eval_params.piece_values[Pieces::ROOK] {
+                0
+            } else {
+                SCALE_NORMAL / 8
+            };
+        }
+
+        // Rook pawns with a bishop that can't cover the promotion square, against a king that
+        // got to the corner first
+        let strong_pawns = pieces(strong, Pieces::PAWN);
+        let bishop = pieces(strong, Pieces::BISHOP);
+        if only(strong, Pieces::BISHOP) && board.bb_side[weak] == pieces(weak, Pieces::KING) {
+            let promotion_rank: Bitboard = if strong == Sides::WHITE { 0xff } else { 0xff << 56 };
+            let king = pieces(weak, Pieces::KING).trailing_zeros() as usize;
+            for file in [FILE_A, FILE_H] {
+                if strong_pawns & !file != 0 {
+                    continue;
+                }
+                let promotion = (file & promotion_rank).trailing_zeros() as usize;
+                let distance =
+                    (promotion / 8).abs_diff(king / 8).max((promotion % 8).abs_diff(king % 8));
+                let wrong_color =
+                    (bishop & LIGHT_SQUARES == 0) != (LIGHT_SQUARES >> promotion & 1 == 0);
+                if wrong_color && distance <= 1 {
+                    return 0;
+                }
+            }
+        }
+
+        // Bishops of opposite colors, the weaker side blockades on the color the other can't reach
+        let weak_bishop = pieces(weak, Pieces::BISHOP);
+        if bishop.count_ones() == 1
+            && weak_bishop.count_ones() == 1
+            && (bishop & LIGHT_SQUARES == 0) != (weak_bishop & LIGHT_SQUARES == 0)
+        {
+            return if non_pawn(strong) == bishop && non_pawn(weak) == weak_bishop {
+                SCALE_NORMAL / 2
+            } else {
+                SCALE_NORMAL * 3 / 4
+            };
+        }
+
+        // Rook endings with at most a pawn more are mostly held
+        if only(strong, Pieces::ROOK)
+            && only(weak, Pieces::ROOK)
+            && strong_pawns.count_ones() <= pieces(weak, Pieces::PAWN).count_ones() + 1
+        {
+            return SCALE_NORMAL / 2;
+        }
+
+        SCALE_NORMAL
+    }
+
+    pub fn material(&self, board: &Board) -> i32 {
+        let mut eval = 0;
+        for piece in 0..6 {
+            let white = board.bb_pieces[Sides::WHITE][piece].count_ones() as i32;
+            let black = board.bb_pieces[Sides::BLACK][piece].count_ones() as i32;
+            eval += (white - black) * self.eval_params.piece_values[piece];
+        }
+
+        eval
+    }
+
+    // Penalty for each file with more than one pawn
     pub fn double_pawn_penalty(&self, board: &Board) -> i32 {
         let mut eval = 0;
         const FILE_MASK: u64 = 0x101010101010101;
@@ -414,11 +1157,11 @@ impl Search {
         for file in 0..8 {
             let filemask = FILE_MASK >> file;
             if (white_pawns & filemask).count_ones() > 1 {
-                eval -= 40;
+                eval -= self.eval_params.double_pawn_penalty;
             }
             
             if (black_pawns & filemask).count_ones() > 1 {
-                eval += 40;
+                eval += self.eval_params.double_pawn_penalty;
             }
         }
 
@@ -454,9 +1197,9 @@ impl Search {
         let phase_values = [0, 1, 1, 2, 4, 0];
         let mut phase: i32 = 24;
 
-        for side in 0..2 {
-            for piece in 0..6 {
-                phase -= phase_values[piece] * (board.bb_pieces[side][piece].count_ones() as i32);
+        for pieces in &board.bb_pieces {
+            for (value, bb) in phase_values.iter().zip(pieces) {
+                phase -= value * (bb.count_ones() as i32);
             }
         }
 
@@ -467,7 +1210,7 @@ impl Search {
         let mut eval: i32 = 0;
         let mut white = board.bb_side[Sides::WHITE];
 
-        let scores = [100, 320, 300, 500, 900, 0];
+        let scores = self.eval_params.piece_values;
         while let Some(square) = bitscan_forward(white) {
             white &= white - 1;
             if let Some(piece) = board.get_piece_at(square) {
@@ -486,3 +1229,25 @@ impl Search {
         eval
     }
 }
+
+// Mate scores are stored relative to the node instead of the root, so they stay right when the
+// position is reached at another ply
+fn score_to_tt(score: i32, ply: usize) -> i32 {
+    if score > INF - MAX_DEPTH as i32 * 2 {
+        score + ply as i32
+    } else if score < -INF + MAX_DEPTH as i32 * 2 {
+        score - ply as i32
+    } else {
+        score
+    }
+}
+
+fn score_from_tt(score: i32, ply: usize) -> i32 {
+    if score > INF - MAX_DEPTH as i32 * 2 {
+        score - ply as i32
+    } else if score < -INF + MAX_DEPTH as i32 * 2 {
+        score + ply as i32
+    } else {
+        score
+    }
+}
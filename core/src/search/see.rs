@@ -0,0 +1,126 @@
+use crate::{
+    board::{
+        board::Board,
+        defs::{Pieces, Sides},
+    },
+    movegen::{
+        movegen::{bishop_attacks, bitscan_forward, rook_attacks, MoveGen},
+        moves::Move,
+    },
+};
+
+// Least valuable first, the order in which pieces join an exchange
+const EXCHANGE_ORDER: [usize; 6] = [
+    Pieces::PAWN,
+    Pieces::KNIGHT,
+    Pieces::BISHOP,
+    Pieces::ROOK,
+    Pieces::QUEEN,
+    Pieces::KING,
+];
+
+// Static exchange evaluation: whether `mv` wins at least `threshold` material once both sides
+// have recaptured on the target square with their least valuable pieces, as long as it pays.
+// Sliders lined up behind an attacker join in once it has captured. Pieces are worth
+// `piece_values`, the evaluation's, and promotions count what the pawn turns into.
+pub fn see_ge(
+    mg: &MoveGen,
+    board: &Board,
+    mv: Move,
+    threshold: i32,
+    piece_values: &[i32; 6],
+) -> bool {
+    if mv.is_castle() {
+        return threshold <= 0;
+    }
+
+    let from = mv.from();
+    let to = mv.to();
+
+    // En passant moves don't record the pawn they capture
+    let captured = if mv.is_enpassant() {
+        Some(Pieces::PAWN)
+    } else {
+        mv.capture()
+    };
+
+    // A promoted pawn gains the difference, and is recaptured as the new piece
+    let (moved, promotion_gain) = if mv.is_promotion() {
+        let piece = mv.promotion_piece();
+        (piece, piece_values[piece] - piece_values[Pieces::PAWN])
+    } else {
+        (mv.piece(), 0)
+    };
+
+    // What we win if nothing recaptures, then what we lose if the mover is taken
+    let mut swap = captured.map_or(0, |piece| piece_values[piece]) + promotion_gain - threshold;
+    if swap < 0 {
+        return false;
+    }
+    swap = piece_values[moved] - swap;
+    if swap <= 0 {
+        return true;
+    }
+
+    let mut occupied = board.bb_side[Sides::BOTH] & !(1 << from);
+    if mv.is_enpassant() {
+        let captured = if board.us() == Sides::WHITE { to + 8 } else { to - 8 };
+        occupied &= !(1 << captured);
+    }
+
+    let diagonal = board.bb_pieces[Sides::WHITE][Pieces::BISHOP]
+        | board.bb_pieces[Sides::WHITE][Pieces::QUEEN]
+        | board.bb_pieces[Sides::BLACK][Pieces::BISHOP]
+        | board.bb_pieces[Sides::BLACK][Pieces::QUEEN];
+    let orthogonal = board.bb_pieces[Sides::WHITE][Pieces::ROOK]
+        | board.bb_pieces[Sides::WHITE][Pieces::QUEEN]
+        | board.bb_pieces[Sides::BLACK][Pieces::ROOK]
+        | board.bb_pieces[Sides::BLACK][Pieces::QUEEN];
+
+    let mut attackers = mg.attackers_to(board, to, occupied);
+    let mut side = board.us();
+    // 1 while the side that moved last is winning the exchange
+    let mut result = 1;
+
+    loop {
+        side ^= 1;
+        attackers &= occupied;
+
+        let our_attackers = attackers & board.bb_side[side];
+        if our_attackers == 0 {
+            break;
+        }
+        result ^= 1;
+
+        let piece = EXCHANGE_ORDER
+            .into_iter()
+            .find(|&piece| our_attackers & board.bb_pieces[side][piece] != 0)
+            .unwrap();
+
+        // The king can only recapture if nothing can take it back
+        if piece == Pieces::KING {
+            if attackers & board.bb_side[side ^ 1] != 0 {
+                result ^= 1;
+            }
+            break;
+        }
+
+        swap = piece_values[piece] - swap;
+        if swap < result {
+            break;
+        }
+
+        let square = bitscan_forward(our_attackers & board.bb_pieces[side][piece]).unwrap();
+        occupied &= !(1 << square);
+
+        // Uncover x-rays behind the piece that just captured
+        if matches!(piece, Pieces::PAWN | Pieces::BISHOP | Pieces::QUEEN) {
+            attackers |= bishop_attacks(to, occupied) & diagonal;
+        }
+        if matches!(piece, Pieces::ROOK | Pieces::QUEEN) {
+            attackers |= rook_attacks(to, occupied) & orthogonal;
+        }
+    }
+
+    result == 1
+}
@@ -1,6 +1,9 @@
-use crate::movegen::{movelist::MoveList, moves::Move};
+use crate::{
+    board::defs::Side,
+    movegen::{movelist::MoveList, moves::Move},
+};
 
-use super::defs::INF;
+use super::{defs::INF, heuristics::Heuristics};
 
 pub fn sort_moves(moves: &mut MoveList, hash_move: Option<Move>) {
     let points = [1, 3, 3, 5, 9, 0];
@@ -16,13 +19,25 @@ pub fn sort_moves(moves: &mut MoveList, hash_move: Option<Move>) {
     });
 }
 
-pub fn retain_captures(moves: MoveList) -> MoveList {
-    let mut captures = MoveList::new();
-    for mv in moves.iter() {
-        if mv.capture().is_some() {
-            captures.push(*mv);
-        };
-    }
+// MVV-LVA, with the capture history deciding between captures of about the same worth
+pub fn score_capture(mv: Move, heuristics: &Heuristics) -> i32 {
+    let points = [1, 3, 3, 5, 9, 0];
+    // En passant doesn't record the pawn it captures
+    let victim = if mv.is_enpassant() {
+        points[0]
+    } else {
+        mv.capture().map_or(0, |piece| points[piece])
+    };
+    let promotion = if mv.is_promotion() { points[mv.promotion_piece()] } else { 0 };
+    ((victim + promotion) * 10 - points[mv.piece()]) * 2048 + heuristics.capture_history(mv)
+}
 
-    captures
-}
\ No newline at end of file
+// Butterfly and continuation history, `previous` holds the moves one and two plies back
+pub fn score_quiet(
+    mv: Move,
+    side: Side,
+    heuristics: &Heuristics,
+    previous: [Option<Move>; 2],
+) -> i32 {
+    heuristics.quiet_history(side, mv, previous)
+}
@@ -0,0 +1,98 @@
+use std::ops::{Index, IndexMut};
+
+use crate::movegen::moves::Move;
+
+use super::defs::MAX_DEPTH;
+
+// What the search knows about one ply of the line it is currently in
+#[derive(Clone, Copy, Default)]
+pub struct StackEntry {
+    // Move being searched from this ply, None for a null move
+    pub current_move: Option<Move>,
+    // Only meaningful when not in check
+    pub static_eval: i32,
+    pub in_check: bool,
+    // Two quiet moves that recently caused a beta cutoff at this ply
+    pub killers: [Option<Move>; 2],
+    // Hash move left out by the singular extension search
+    pub excluded: Option<Move>,
+    // Extensions used on the way to this ply
+    pub extensions: usize,
+}
+
+// Per ply state of the current line, indexed by ply
+pub struct SearchStack {
+    entries: [StackEntry; MAX_DEPTH],
+}
+
+impl Default for SearchStack {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SearchStack {
+    pub fn new() -> SearchStack {
+        SearchStack {
+            entries: [StackEntry::default(); MAX_DEPTH],
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.entries = [StackEntry::default(); MAX_DEPTH];
+    }
+
+    // Move that led to the position at `ply`, None at the root and after a null move
+    pub fn previous_move(&self, ply: usize) -> Option<Move> {
+        ply.checked_sub(1)
+            .and_then(|previous| self.entries[previous].current_move)
+    }
+
+    // Moves one and two plies before `ply`, for the continuation history
+    pub fn continuations(&self, ply: usize) -> [Option<Move>; 2] {
+        [self.previous_move(ply), ply.checked_sub(1).and_then(|ply| self.previous_move(ply))]
+    }
+
+    // Whether the static eval got better since our last move. When we were in check then, the
+    // move before is compared, and without any eval to compare to it counts as improving.
+    pub fn improving(&self, ply: usize) -> bool {
+        let entry = &self.entries[ply];
+        if entry.in_check {
+            return false;
+        }
+
+        [2, 4]
+            .iter()
+            .filter_map(|&back| ply.checked_sub(back))
+            .map(|previous| &self.entries[previous])
+            .find(|previous| !previous.in_check)
+            .is_none_or(|previous| entry.static_eval > previous.static_eval)
+    }
+
+    pub fn is_killer(&self, ply: usize, mv: Move) -> Option<usize> {
+        let killers = self.entries.get(ply)?.killers;
+        killers.iter().position(|&killer| killer == Some(mv))
+    }
+
+    pub fn update_killers(&mut self, ply: usize, mv: Move) {
+        let killers = &mut self.entries[ply].killers;
+        if killers[0] != Some(mv) {
+            killers[1] = killers[0];
+            killers[0] = Some(mv);
+        }
+    }
+}
+
+impl Index<usize> for SearchStack {
+    type Output = StackEntry;
+
+    fn index(&self, ply: usize) -> &StackEntry {
+        &self.entries[ply]
+    }
+}
+
+impl IndexMut<usize> for SearchStack {
+    fn index_mut(&mut self, ply: usize) -> &mut StackEntry {
+        &mut self.entries[ply]
+    }
+}
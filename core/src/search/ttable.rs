@@ -1,6 +1,9 @@
-use std::vec;
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
 
-use crate::{board::defs::ZobristHash, movegen::moves::Move};
+use crate::{
+    board::{board::Board, defs::ZobristHash},
+    movegen::moves::Move,
+};
 
 #[derive(Clone, PartialEq)]
 pub enum MoveType {
@@ -10,54 +13,236 @@ pub enum MoveType {
 }
 
 #[derive(Clone)]
-// 29 Byte entry
-// TODO: make move 32 bits, make depth a u8 (will make tt less than 25 bytes, amazing!)
 pub struct TranspositionEntry {
     pub key: ZobristHash,
-    pub best_move: Move,
+    // Compact move, see `Move::compact`, 0 when no move was searched
+    pub best_move: u16,
     pub eval: i32,
+    // Static evaluation of the position, so it isn't computed again
+    pub static_eval: i32,
     pub depth: usize,
     pub move_type: MoveType,
 }
 
+impl TranspositionEntry {
+    pub fn new(
+        key: ZobristHash,
+        best_move: Option<Move>,
+        eval: i32,
+        static_eval: i32,
+        depth: usize,
+        move_type: MoveType,
+    ) -> Self {
+        TranspositionEntry {
+            key,
+            best_move: best_move.map_or(0, |mv| mv.compact()),
+            eval,
+            static_eval,
+            depth,
+            move_type,
+        }
+    }
+
+    pub fn best_move(&self, board: &Board) -> Option<Move> {
+        Move::from_compact(self.best_move, board)
+    }
+}
+
+// Bit layout of PackedEntry::data. Bound 0 marks an empty slot.
+const EVAL_SHIFT: u64 = 16;
+const DEPTH_SHIFT: u64 = 32;
+const BOUND_SHIFT: u64 = 40;
+const GENERATION_SHIFT: u64 = 42;
+const GENERATION_MASK: u8 = 0x3f;
+const STATIC_EVAL_SHIFT: u64 = 48;
+
+fn pack(entry: &TranspositionEntry, generation: u8) -> u64 {
+    let bound = match entry.move_type {
+        MoveType::Maximum => 1,
+        MoveType::Minimum => 2,
+        MoveType::Exact => 3,
+    };
+
+    entry.best_move as u64
+        | (entry.eval as i16 as u16 as u64) << EVAL_SHIFT
+        | (entry.depth.min(u8::MAX as usize) as u64) << DEPTH_SHIFT
+        | bound << BOUND_SHIFT
+        | (generation as u64) << GENERATION_SHIFT
+        | (entry.static_eval as i16 as u16 as u64) << STATIC_EVAL_SHIFT
+}
+
+fn unpack(key: ZobristHash, data: u64) -> TranspositionEntry {
+    TranspositionEntry {
+        key,
+        best_move: data as u16,
+        eval: (data >> EVAL_SHIFT) as u16 as i16 as i32,
+        static_eval: (data >> STATIC_EVAL_SHIFT) as u16 as i16 as i32,
+        depth: (data >> DEPTH_SHIFT) as u8 as usize,
+        move_type: match data >> BOUND_SHIFT & 0b11 {
+            1 => MoveType::Maximum,
+            2 => MoveType::Minimum,
+            _ => MoveType::Exact,
+        },
+    }
+}
+
+fn is_empty(data: u64) -> bool {
+    data >> BOUND_SHIFT & 0b11 == 0
+}
+
+fn generation_of(data: u64) -> u8 {
+    (data >> GENERATION_SHIFT) as u8 & GENERATION_MASK
+}
+
+// One slot of the table. The key is stored xor'ed with the data, so an entry torn by two threads
+// writing at once fails the key check instead of handing out a mix of both.
+pub struct PackedEntry {
+    key: AtomicU64,
+    data: AtomicU64,
+}
+
+const BUCKET_SIZE: usize = 4;
+
+// Entries sharing an index, exactly one cache line
+#[repr(align(64))]
+pub struct Bucket {
+    entries: [PackedEntry; BUCKET_SIZE],
+}
+
+// Shared by all search threads without locking
 pub struct TranspositionTable {
-    table: Vec<TranspositionEntry>,
-    max_size: usize,
+    buckets: Vec<Bucket>,
+    // Bumped every search, entries from older searches are replaced first
+    generation: AtomicU8,
 }
 
 impl TranspositionTable {
-    pub fn new(max_size: usize) -> Self {
-        let table = vec![
-            TranspositionEntry {
-                key: 0,
-                best_move: Move(0),
-                eval: 0,
-                depth: 0,
-                move_type: MoveType::Exact
-            };
-            max_size
-        ];
+    pub fn new(size_mb: usize) -> Self {
+        let count = (size_mb * 1024 * 1024 / size_of::<Bucket>()).max(1);
+        let buckets = (0..count)
+            .map(|_| Bucket {
+                entries: std::array::from_fn(|_| PackedEntry {
+                    key: AtomicU64::new(0),
+                    data: AtomicU64::new(0),
+                }),
+            })
+            .collect();
+
+        TranspositionTable {
+            buckets,
+            generation: AtomicU8::new(0),
+        }
+    }
 
-        TranspositionTable { table, max_size }
+    pub fn clear(&self) {
+        for entry in self.buckets.iter().flat_map(|bucket| &bucket.entries) {
+            entry.key.store(0, Ordering::Relaxed);
+            entry.data.store(0, Ordering::Relaxed);
+        }
+        self.generation.store(0, Ordering::Relaxed);
+    }
+
+    pub fn new_search(&self) {
+        let generation = self.generation.load(Ordering::Relaxed);
+        self.generation.store(
+            generation.wrapping_add(1) & GENERATION_MASK,
+            Ordering::Relaxed,
+        );
     }
 
     pub fn get(&self, hash: ZobristHash) -> Option<TranspositionEntry> {
-        let index = self.index(hash);
-        let entry = &self.table[index];
+        self.bucket(hash).entries.iter().find_map(|entry| {
+            let data = entry.data.load(Ordering::Relaxed);
+            let key = entry.key.load(Ordering::Relaxed) ^ data;
+            (key == hash && !is_empty(data)).then(|| unpack(key, data))
+        })
+    }
+
+    // Replaces the entry of the same position, unless it is much deeper and from this search.
+    // Otherwise an empty slot is taken, and only then the shallowest entry goes, counting each
+    // search it is old as 8 plies less. The whole bucket is checked for the same position, since
+    // with several threads it can sit behind an empty slot.
+    pub fn insert(&self, mut entry: TranspositionEntry) {
+        let generation = self.generation.load(Ordering::Relaxed);
+        let bucket = self.bucket(entry.key);
 
-        if entry.key == hash {
-            Some(entry.clone())
-        } else {
-            None
+        let mut same = None;
+        let mut empty = None;
+        let mut replace = &bucket.entries[0];
+        let mut worst = i32::MAX;
+        for slot in &bucket.entries {
+            let data = slot.data.load(Ordering::Relaxed);
+            let key = slot.key.load(Ordering::Relaxed) ^ data;
+
+            if is_empty(data) {
+                empty = empty.or(Some(slot));
+                continue;
+            }
+
+            let age = generation.wrapping_sub(generation_of(data)) & GENERATION_MASK;
+            let stored = unpack(key, data);
+            let depth = stored.depth;
+            if key == entry.key {
+                if age == 0 && entry.move_type != MoveType::Exact && entry.depth + 4 < depth {
+                    return;
+                }
+                // An entry without a move, like a stand pat, keeps the move searched before
+                if entry.best_move == 0 {
+                    entry.best_move = stored.best_move;
+                }
+                same = Some(slot);
+                break;
+            }
+
+            let value = depth as i32 - 8 * age as i32;
+            if value < worst {
+                worst = value;
+                replace = slot;
+            }
         }
+
+        let replace = same.or(empty).unwrap_or(replace);
+        let data = pack(&entry, generation);
+        replace.key.store(entry.key ^ data, Ordering::Relaxed);
+        replace.data.store(data, Ordering::Relaxed);
     }
 
-    pub fn insert(&mut self, entry: TranspositionEntry) {
-        let index = self.index(entry.key);
-        self.table[index] = entry;
+    // Hints the CPU to start loading the bucket of a position that will be probed soon
+    pub fn prefetch(&self, hash: ZobristHash) {
+        #[cfg(target_arch = "x86_64")]
+        {
+            use std::arch::x86_64::{_mm_prefetch, _MM_HINT_T0};
+            let bucket = self.bucket(hash) as *const Bucket as *const i8;
+            // SAFETY: prefetching has no observable effect, the pointer isn't even dereferenced
+            unsafe { _mm_prefetch::<_MM_HINT_T0>(bucket) };
+        }
+        #[cfg(not(target_arch = "x86_64"))]
+        let _ = hash;
+    }
+
+    // Permille of sampled entries written during the current search, for UCI's hashfull
+    pub fn hashfull(&self) -> usize {
+        let generation = self.generation.load(Ordering::Relaxed);
+        let sample = self
+            .buckets
+            .iter()
+            .take(1000 / BUCKET_SIZE)
+            .flat_map(|bucket| &bucket.entries);
+
+        let (mut total, mut used) = (0, 0);
+        for entry in sample {
+            let data = entry.data.load(Ordering::Relaxed);
+            total += 1;
+            if !is_empty(data) && generation_of(data) == generation {
+                used += 1;
+            }
+        }
+        used * 1000 / total
     }
 
-    fn index(&self, key: ZobristHash) -> usize {
-        (key as usize) % self.max_size
+    // Multiply-shift maps the hash evenly onto any number of buckets
+    fn bucket(&self, hash: ZobristHash) -> &Bucket {
+        let index = (hash as u128 * self.buckets.len() as u128) >> 64;
+        &self.buckets[index as usize]
     }
 }
@@ -0,0 +1,379 @@
+use core::{
+    board::{
+        board::{algebraic_to_square, parse_square, Board, ZobristRandoms},
+        cuckoo::Cuckoo,
+        defs::{GameResult, Sides, START_POS},
+    },
+    movegen::movegen::MoveGen,
+    search::search::Search,
+};
+
+const POSITIONS: [&str; 5] = [
+    START_POS,
+    "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+    "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+    "rnbqkbnr/ppp1pppp/8/8/3pP3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1",
+    "r3k3/8/8/8/8/8/8/4K2R b Kq - 0 1",
+];
+
+#[test]
+fn test_fen_round_trip() {
+    for fen in POSITIONS {
+        let board = Board::from_fen(fen).unwrap();
+        assert_eq!(board.to_fen(), fen);
+    }
+}
+
+#[test]
+fn test_chess960_fen() {
+    // Shredder-FEN files are written back as K/Q when the rook is the outermost one
+    let board = Board::from_fen("bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9")
+        .unwrap();
+    assert_eq!(
+        board.to_fen(),
+        "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w KQkq - 2 9"
+    );
+
+    // Inner rooks keep their file
+    let fen = "1r2k1r1/8/8/8/8/8/8/RR2K3 w Bkq - 0 1";
+    let board = Board::from_fen(fen).unwrap();
+    assert_eq!(board.to_fen(), fen);
+    assert_eq!(board.castling_rooks, [[63, 57], [6, 1]]);
+
+    assert!(Board::from_fen("4k3/8/8/8/8/8/8/4K3 w K - 0 1").is_err());
+    assert!(Board::from_fen("4k3/8/8/8/8/8/8/4K2R w C - 0 1").is_err());
+}
+
+#[test]
+fn test_mirrored() {
+    let mg = MoveGen;
+    let search = Search::new(1);
+
+    for fen in POSITIONS {
+        let mut board = Board::from_fen(fen).unwrap();
+        let mut mirrored = board.mirrored();
+
+        assert_eq!(mirrored.mirrored().to_fen(), fen);
+        assert_eq!(mirrored.us(), board.them());
+        assert_eq!(mg.perft(&mut mirrored, 3), mg.perft(&mut board, 3));
+        assert_eq!(
+            search.static_eval(&mut mirrored),
+            search.static_eval(&mut board)
+        );
+    }
+}
+
+#[test]
+fn test_mirrored_matches_fen() {
+    let board = Board::from_fen("r3k3/8/8/8/8/8/8/4K2R b Kq - 0 1").unwrap();
+    let expected = Board::from_fen("4k2r/8/8/8/8/8/8/R3K3 w Qk - 0 1").unwrap();
+
+    assert_eq!(board.mirrored().to_fen(), expected.to_fen());
+    assert_eq!(board.mirrored().zobrist_hash(), expected.zobrist_hash());
+}
+
+#[test]
+fn test_eval_breakdown() {
+    let search = Search::new(1);
+    let mut board = Board::from_fen("r3k3/pp6/8/8/8/8/PPP5/3QK3 b - - 0 1").unwrap();
+
+    let breakdown = search.eval_breakdown(&board);
+    assert_eq!(breakdown.material, 500);
+    assert_eq!(breakdown.scale, 128);
+    assert_eq!(
+        breakdown.total,
+        breakdown.material + breakdown.psqt + breakdown.double_pawns
+    );
+    // Black to move
+    assert_eq!(search.static_eval(&mut board), -breakdown.total);
+}
+
+// Follows every line to `depth`, comparing the updated key with one computed from scratch
+fn check_zobrist(board: &mut Board, mg: &MoveGen, depth: usize) {
+    assert_eq!(
+        board.zobrist_hash(),
+        Board::from_fen(&board.to_fen()).unwrap().zobrist_hash(),
+        "{}",
+        board.to_fen()
+    );
+    if depth == 0 {
+        return;
+    }
+
+    for mv in mg.gen_legal_moves(board).iter() {
+        board.do_move(mv);
+        check_zobrist(board, mg, depth - 1);
+        board.undo_move(mv);
+    }
+}
+
+#[test]
+fn test_incremental_zobrist() {
+    let mg = MoveGen;
+    for fen in POSITIONS {
+        let mut board = Board::from_fen(fen).unwrap();
+        let hash = board.zobrist_hash();
+        check_zobrist(&mut board, &mg, 3);
+        assert_eq!(board.zobrist_hash(), hash);
+    }
+
+    // Including a Chess960 position with castles, and promotions
+    for fen in [
+        "1r2k1r1/8/8/8/8/8/8/RR2K3 w Bkq - 0 1",
+        "n1n5/PPPk4/8/8/8/8/4Kppp/5N1N b - - 0 1",
+    ] {
+        let mut board = Board::from_fen(fen).unwrap();
+        check_zobrist(&mut board, &mg, 3);
+    }
+}
+
+#[test]
+fn test_null_move() {
+    // The en passant square goes away with the turn
+    let fen = "rnbqkbnr/ppp1pppp/8/8/3pP3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1";
+    let passed = "rnbqkbnr/ppp1pppp/8/8/3pP3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 1";
+    let mut board = Board::from_fen(fen).unwrap();
+    let hash = board.zobrist_hash();
+
+    board.do_null_move();
+    assert_eq!(board.zobrist_hash(), Board::from_fen(passed).unwrap().zobrist_hash());
+    assert_eq!(board.history.count_hash(board.zobrist_hash()), 1);
+
+    // Passing back and forth repeats the position, without the en passant square
+    board.do_null_move();
+    let repeated = board.zobrist_hash();
+    board.do_null_move();
+    board.do_null_move();
+    assert_eq!(board.history.count_hash(repeated), 2);
+    for _ in 0..3 {
+        board.undo_null_move();
+    }
+
+    board.undo_null_move();
+    assert_eq!(board.zobrist_hash(), hash);
+    assert_eq!(board.game_state.enpassant_piece, Some(44));
+    assert_eq!(board.history.count_hash(board.zobrist_hash()), 0);
+}
+
+#[test]
+fn test_repetition() {
+    let mg = MoveGen;
+    let mut board = Board::from_fen(START_POS).unwrap();
+    let play = |board: &mut Board, moves: &[(usize, usize)]| {
+        for &(from, to) in moves {
+            let mv = *mg
+                .gen_legal_moves(board)
+                .iter()
+                .find(|mv| mv.from() == from && mv.to() == to)
+                .unwrap();
+            board.do_move(&mv);
+        }
+    };
+    // Nf3 Nf6 Ng1 Ng8
+    let shuffle = [(62, 45), (6, 21), (45, 62), (21, 6)];
+
+    // The second time only counts inside the search tree, the root being 4 plies back or more
+    play(&mut board, &shuffle);
+    assert!(!board.is_repetition(0));
+    assert!(!board.is_repetition(4));
+    assert!(board.is_repetition(5));
+
+    // The third time is a draw anywhere
+    play(&mut board, &shuffle);
+    assert!(board.is_repetition(0));
+
+    // An irreversible move cuts the history off, e3 e6
+    play(&mut board, &[(52, 44), (12, 20)]);
+    play(&mut board, &shuffle);
+    assert!(!board.is_repetition(0));
+    assert!(board.is_repetition(5));
+
+    // So does a null move, passing with Nf6 and Ng8 in between gets the position back
+    board.do_null_move();
+    play(&mut board, &[(6, 21)]);
+    board.do_null_move();
+    play(&mut board, &[(21, 6)]);
+    assert!(!board.is_repetition(5));
+}
+
+#[test]
+fn test_game_cycle() {
+    let mg = MoveGen;
+    let cuckoo = Cuckoo::new(&ZobristRandoms::new());
+    let play = |board: &mut Board, moves: &[(usize, usize)]| {
+        for &(from, to) in moves {
+            let mv = *mg
+                .gen_legal_moves(board)
+                .iter()
+                .find(|mv| mv.from() == from && mv.to() == to)
+                .unwrap();
+            board.do_move(&mv);
+        }
+    };
+
+    // Nf3 Nf6 Ng1, and Ng8 gets back to the start
+    let mut board = Board::from_fen(START_POS).unwrap();
+    play(&mut board, &[(62, 45), (6, 21), (45, 62)]);
+    assert!(board.has_game_cycle(4, &cuckoo));
+    // Before the root the position has to have repeated already
+    assert!(!board.has_game_cycle(0, &cuckoo));
+    play(&mut board, &[(21, 6), (62, 45), (6, 21), (45, 62)]);
+    assert!(board.has_game_cycle(0, &cuckoo));
+
+    // Ra1 b1 b4 a4, with the kings shuffling, is only a cycle without the pawn on a3 in the way
+    let rook_tour = [(56, 57), (4, 3), (57, 33), (3, 4), (33, 32)];
+    let mut board = Board::from_fen("4k3/8/8/8/8/8/8/R3K3 w - - 0 1").unwrap();
+    play(&mut board, &rook_tour);
+    assert!(board.has_game_cycle(6, &cuckoo));
+    let mut board = Board::from_fen("4k3/8/8/8/8/P7/8/R3K3 w - - 0 1").unwrap();
+    play(&mut board, &rook_tour);
+    assert!(!board.has_game_cycle(6, &cuckoo));
+}
+
+#[test]
+fn test_halfmove_clock() {
+    let mg = MoveGen;
+    let fen = "4k3/8/8/8/8/8/4P3/R3K3 w - - 98 1";
+    let mut board = Board::from_fen(fen).unwrap();
+    assert_eq!(board.to_fen(), fen);
+    assert!(!board.is_fifty_move_draw());
+
+    let moves = mg.gen_legal_moves(&mut board);
+    let rook_move = *moves.iter().find(|mv| mv.from() == 56 && mv.to() == 48).unwrap();
+    let pawn_move = *moves.iter().find(|mv| mv.from() == 52 && mv.to() == 44).unwrap();
+
+    board.do_move(&rook_move);
+    assert_eq!(board.game_state.halfmove_clock, 99);
+    board.undo_move(&rook_move);
+    board.do_move(&pawn_move);
+    assert_eq!(board.game_state.halfmove_clock, 0);
+    board.undo_move(&pawn_move);
+
+    board.do_null_move();
+    board.do_null_move();
+    assert!(board.is_fifty_move_draw());
+
+    assert!(Board::from_fen("4k3/8/8/8/8/8/8/4K3 w - - x 1").is_err());
+    assert_eq!(Board::from_fen("4k3/8/8/8/8/8/8/4K3 w - -").unwrap().game_state.halfmove_clock, 0);
+}
+
+#[test]
+fn test_fullmove_number() {
+    let mg = MoveGen;
+    let mut board = Board::from_fen("4k3/8/8/8/8/8/8/R3K3 w - - 0 12").unwrap();
+    let rook_move = *mg.gen_legal_moves(&mut board).iter().find(|mv| mv.from() == 56).unwrap();
+    board.do_move(&rook_move);
+    assert!(board.to_fen().ends_with(" 1 12"));
+    let king_move = mg.gen_legal_moves(&mut board).moves[0];
+    board.do_move(&king_move);
+    assert!(board.to_fen().ends_with(" 2 13"));
+    board.undo_move(&king_move);
+    assert!(board.to_fen().ends_with(" 1 12"));
+
+    // Optional like the halfmove clock, and never below 1
+    let fullmove = |fen: &str| Board::from_fen(fen).unwrap().game_state.fullmove_number;
+    assert_eq!(fullmove("4k3/8/8/8/8/8/8/4K3 w - - 0"), 1);
+    assert_eq!(fullmove("4k3/8/8/8/8/8/8/4K3 w - - 0 0"), 1);
+    assert!(Board::from_fen("4k3/8/8/8/8/8/8/4K3 w - - 0 x").is_err());
+}
+
+#[test]
+fn test_insufficient_material() {
+    for fen in [
+        "4k3/8/8/8/8/8/8/4K3 w - - 0 1",
+        "4k3/8/8/8/8/8/8/4KN2 w - - 0 1",
+        "4k3/8/8/8/8/8/8/4KB2 b - - 0 1",
+        // Bishops on light squares only, whoever they belong to
+        "2b1k3/8/8/8/8/8/8/4KB2 w - - 0 1",
+        "4k3/8/8/8/8/8/8/B1B1K3 w - - 0 1",
+    ] {
+        assert!(Board::from_fen(fen).unwrap().is_insufficient_material(), "{fen}");
+    }
+
+    // Mates exist, however unlikely
+    for fen in [
+        "4k3/8/8/8/8/8/8/2B1KB2 w - - 0 1",
+        "4k3/8/8/8/8/8/8/4KNN1 w - - 0 1",
+        "4kn2/8/8/8/8/8/8/4KB2 w - - 0 1",
+        "4k3/8/8/8/8/8/7p/4K3 w - - 0 1",
+        "4k3/8/8/8/8/8/8/4K2R w - - 0 1",
+    ] {
+        assert!(!Board::from_fen(fen).unwrap().is_insufficient_material(), "{fen}");
+    }
+}
+
+#[test]
+fn test_game_result() {
+    let mg = MoveGen;
+    let result = |fen: &str| Board::from_fen(fen).unwrap().game_result(&mg);
+
+    assert_eq!(result(START_POS), None);
+    let mate = result("R5k1/5ppp/8/8/8/8/8/6K1 b - - 0 1").unwrap();
+    assert_eq!(mate, GameResult::Checkmate(Sides::WHITE));
+    assert_eq!(mate.to_string(), "1-0 {White mates}");
+    // Mate on the hundred and fiftieth ply still counts
+    assert_eq!(
+        result("R5k1/5ppp/8/8/8/8/8/6K1 b - - 150 1"),
+        Some(GameResult::Checkmate(Sides::WHITE))
+    );
+    assert_eq!(result("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1"), Some(GameResult::Stalemate));
+    assert_eq!(
+        result("4k3/8/8/8/8/8/8/4KN2 w - - 0 1"),
+        Some(GameResult::InsufficientMaterial)
+    );
+    // The fifty-move rule is only a claim, seventy-five moves end the game
+    assert_eq!(result("4k3/8/8/8/8/8/8/R3K3 w - - 100 1"), None);
+    assert_eq!(
+        result("4k3/8/8/8/8/8/8/R3K3 w - - 150 1"),
+        Some(GameResult::SeventyFiveMoves)
+    );
+
+    // Likewise threefold repetition, the game ends the fifth time, Nf3 Nf6 Ng1 Ng8
+    let mut board = Board::from_fen(START_POS).unwrap();
+    for time in 2..=5 {
+        for (from, to) in [(62, 45), (6, 21), (45, 62), (21, 6)] {
+            let mv = *mg
+                .gen_legal_moves(&mut board)
+                .iter()
+                .find(|mv| mv.from() == from && mv.to() == to)
+                .unwrap();
+            board.do_move(&mv);
+        }
+        assert_eq!(board.repetitions(), time);
+        let expected = (time == 5).then_some(GameResult::FivefoldRepetition);
+        assert_eq!(board.game_result(&mg), expected);
+    }
+
+    let draw = GameResult::FivefoldRepetition;
+    assert_eq!(draw.pgn(), "1/2-1/2");
+    assert_eq!(draw.to_string(), "1/2-1/2 {Draw by fivefold repetition}");
+    assert_eq!(GameResult::Checkmate(Sides::BLACK).pgn(), "0-1");
+}
+
+#[test]
+fn test_algebraic_to_square() {
+    assert_eq!(algebraic_to_square("a8"), 0);
+    assert_eq!(algebraic_to_square("h1"), 63);
+    assert_eq!(algebraic_to_square("e3"), 44);
+
+    // The en passant square from a FEN has to be the one the capturing pawn lands on
+    let mg = MoveGen;
+    let fen = "rnbqkbnr/ppp1pppp/8/8/3pP3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1";
+    let mut board = Board::from_fen(fen).unwrap();
+    assert_eq!(board.game_state.enpassant_piece, Some(44));
+    let moves = mg.gen_legal_moves(&mut board);
+    assert!(moves
+        .iter()
+        .any(|mv| mv.is_enpassant() && mv.from() == 35 && mv.to() == 44));
+}
+
+#[test]
+fn test_parse_square() {
+    for square in 0..64 {
+        let alg = format!("{}{}", (b'a' + (square % 8) as u8) as char, 8 - square / 8);
+        assert_eq!(parse_square(&alg), Some(square));
+    }
+    for alg in ["", "e", "e33", "i1", "a0", "a9", "E3"] {
+        assert_eq!(parse_square(alg), None);
+    }
+}
@@ -1,7 +1,7 @@
 use core::{
     board::{
         board::Board,
-        defs::{Pieces, Sides, START_POS},
+        defs::START_POS,
     },
     movegen::movegen::MoveGen,
 };
@@ -62,14 +62,112 @@ fn test_perft_nodes_v2(depth: usize, mg: &MoveGen, board: &mut Board) -> usize {
     let moves = mg.gen_moves(board);
 
     for _move in moves.iter() {
-        board.do_move(&_move);
+        board.do_move(_move);
         if mg.in_check(board, board.them()) {
-            board.undo_move(&_move);
+            board.undo_move(_move);
             continue;
         }
         count += test_perft_nodes_v2(depth - 1, mg, board);
-        board.undo_move(&_move);
+        board.undo_move(_move);
     }
 
     count
 }
+
+// Chess960 positions with Shredder-FEN castling fields
+#[test]
+fn test_chess960() {
+    let mg = MoveGen;
+    let positions: [(&str, [usize; 4]); 10] = [
+        (
+            "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9",
+            [21, 528, 12189, 326672],
+        ),
+        (
+            "2nnrbkr/p1qppppp/8/1ppb4/6PP/3PP3/PPP2P2/BQNNRBKR w HEhe - 1 9",
+            [21, 807, 18002, 667366],
+        ),
+        (
+            "b1q1rrkb/pppppppp/3nn3/8/P7/1PPP4/4PPPP/BQNNRKRB w GE - 1 9",
+            [20, 479, 10471, 273318],
+        ),
+        (
+            "qbbnnrkr/2pp2pp/p7/1p2pp2/8/P3PP2/1PPP1KPP/QBBNNR1R w hf - 0 9",
+            [22, 593, 13440, 382958],
+        ),
+        (
+            "1nbbnrkr/p1p1ppp1/3p4/1p3P1p/3Pq2P/8/PPP1P1P1/QNBBNRKR w HFhf - 0 9",
+            [28, 1120, 31058, 1171749],
+        ),
+        (
+            "qnbnr1kr/ppp1b1pp/4p3/3p1p2/8/2NPP3/PPP1BPPP/QNB1R1KR w HEhe - 1 9",
+            [29, 899, 26578, 824055],
+        ),
+        (
+            "q1bnrkr1/ppppp2p/2n2p2/4b1p1/2NP4/8/PPP1PPPP/QNB1RRKB w ge - 1 9",
+            [30, 860, 24566, 732757],
+        ),
+        (
+            "qbn1brkr/ppp1p1p1/2n4p/3p1p2/P7/6PP/QPPPPP2/1BNNBRKR w HFhf - 0 9",
+            [25, 635, 17054, 465806],
+        ),
+        (
+            "qnnbbrkr/1p2ppp1/2pp3p/p7/1P5P/2NP4/P1P1PPP1/Q1NBBRKR w HFhf - 0 9",
+            [24, 572, 15243, 384260],
+        ),
+        (
+            "qn1rbbkr/ppp2p1p/1n1pp1p1/8/3P4/P6P/1PP1PPPK/QNNRBB1R w hd - 2 9",
+            [28, 811, 23175, 679699],
+        ),
+    ];
+
+    for (fen, counts) in positions {
+        let mut board = Board::from_fen(fen).unwrap();
+        for (depth, &count) in counts.iter().enumerate() {
+            assert_eq!(
+                test_perft_nodes_v2(depth + 1, &mg, &mut board),
+                count,
+                "{} depth {}",
+                fen,
+                depth + 1
+            );
+        }
+    }
+}
+
+// gen_captures must find exactly the tactical moves of gen_moves, in every position of the tree
+fn check_captures(depth: usize, mg: &MoveGen, board: &mut Board) {
+    let mut expected: Vec<usize> = mg
+        .gen_moves(board)
+        .iter()
+        .filter(|mv| mv.is_tactical())
+        .map(|mv| mv.0)
+        .collect();
+    let mut captures: Vec<usize> = mg.gen_captures(board).iter().map(|mv| mv.0).collect();
+    expected.sort();
+    captures.sort();
+    assert_eq!(captures, expected, "{}", board.to_fen());
+
+    if depth == 0 {
+        return;
+    }
+    for mv in mg.gen_legal_moves(board).iter() {
+        board.do_move(mv);
+        check_captures(depth - 1, mg, board);
+        board.undo_move(mv);
+    }
+}
+
+#[test]
+fn test_gen_captures() {
+    let mg = MoveGen;
+    for fen in [
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+        "n1n5/PPPk4/8/8/8/8/4Kppp/5N1N b - - 0 1",
+        "rnbqkbnr/ppp1pppp/8/8/3pP3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1",
+    ] {
+        let mut board = Board::from_fen(fen).unwrap();
+        check_captures(3, &mg, &mut board);
+    }
+}
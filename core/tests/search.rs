@@ -0,0 +1,672 @@
+use core::{
+    board::{
+        board::Board,
+        defs::{Pieces, Sides, START_POS},
+    },
+    movegen::{movegen::MoveGen, moves::Move},
+    search::{
+        defs::{ScoreBound, INF, MAX_DEPTH, PIECE_VALUES, SCALE_NORMAL},
+        heuristics::{Heuristics, MAX_HISTORY},
+        movepicker::{MovePicker, Stage},
+        params::{SearchParams, INTERNAL_DEEPENING, INTERNAL_OFF, INTERNAL_REDUCTIONS},
+        search::Search,
+        see::see_ge,
+        sorting::score_capture,
+        stack::SearchStack,
+        ttable::{MoveType, TranspositionEntry, TranspositionTable},
+    },
+};
+use std::{
+    sync::{atomic::Ordering, Arc, Mutex},
+    time::Duration,
+};
+
+const KIWIPETE: &str = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+// Re8+ Rxe8 Rxe8#
+const BACK_RANK_MATE: &str = "r5k1/5ppp/8/8/8/8/4RPPP/4R1K1 w - - 0 1";
+
+// A fresh single threaded search of `fen`, returning the best move, score and nodes
+fn search_fen(fen: &str, depth: usize, params: &SearchParams) -> (Move, i32, u64) {
+    let mg = MoveGen;
+    let mut board = Board::from_fen(fen).unwrap();
+    let mut search = Search::new(1);
+    search.params = params.clone();
+    let best_move = search.find_best_move_iter(&mut board, &mg, depth, Duration::MAX);
+    (best_move.unwrap(), search.score.unwrap(), search.nodes)
+}
+
+fn assert_back_rank_mate(depth: usize, params: &SearchParams) {
+    let (best_move, score, _) = search_fen(BACK_RANK_MATE, depth, params);
+    assert_eq!((best_move.from(), best_move.to()), (52, 4));
+    assert_eq!(score, INF - 3);
+}
+
+fn find(board: &mut Board, mg: &MoveGen, from: usize, to: usize) -> Move {
+    *mg.gen_legal_moves(board)
+        .iter()
+        .find(|mv| mv.from() == from && mv.to() == to)
+        .unwrap()
+}
+
+#[test]
+fn test_move_picker_order() {
+    let mg = MoveGen;
+    // Pawn takes knight wins material, rook takes the defended pawn on a6 loses it
+    let mut board = Board::from_fen("4k3/1p6/p7/3n4/4P3/8/8/R3K3 w - - 0 1").unwrap();
+    let hash_move = find(&mut board, &mg, 60, 61);
+    let good_capture = find(&mut board, &mg, 36, 27);
+    let bad_capture = find(&mut board, &mg, 56, 16);
+    let killer = find(&mut board, &mg, 56, 48);
+    let counter = find(&mut board, &mg, 56, 40);
+    let good_history = find(&mut board, &mg, 56, 32);
+    let previous = Move(0);
+
+    let mut heuristics = Heuristics::new();
+    heuristics.update_cutoff(Sides::WHITE, 4, good_history, [None; 2], &[]);
+    heuristics.update_cutoff(Sides::WHITE, 1, counter, [Some(previous), None], &[]);
+    heuristics.update_cutoff(Sides::WHITE, 1, killer, [None; 2], &[]);
+    // Replies are kept per side, black's move to the same square has none
+    assert_eq!(heuristics.countermove(Sides::WHITE, Some(previous)), Some(counter));
+    assert_eq!(heuristics.countermove(Sides::BLACK, Some(previous)), None);
+
+    let killers = [Some(killer), None];
+    let previous = [Some(previous), None];
+    let values = PIECE_VALUES;
+    let mut picker =
+        MovePicker::new(&mut board, &mg, Some(hash_move), &heuristics, killers, previous, values);
+    let mut picked = Vec::new();
+    while let Some(mv) = picker.next(&mut board, &mg, &heuristics) {
+        picked.push(mv);
+    }
+
+    assert_eq!(
+        &picked[..5],
+        &[hash_move, good_capture, killer, counter, good_history]
+    );
+    assert_eq!(picked.last(), Some(&bad_capture));
+    assert_eq!(picker.stage, Stage::Done);
+
+    // Every pseudo-legal move exactly once
+    let moves = mg.gen_moves(&mut board);
+    assert_eq!(picked.len(), moves.index);
+    assert!(moves.iter().all(|mv| picked.iter().filter(|&p| p == mv).count() == 1));
+}
+
+#[test]
+fn test_move_picker_rejects_illegal_hash_move() {
+    let mg = MoveGen;
+    let mut board = Board::from_fen(START_POS).unwrap();
+    let e4 = find(&mut board, &mg, 52, 36);
+    let mut other = Board::from_fen("4k3/8/8/8/8/8/8/R3K3 w - - 0 1").unwrap();
+    let rook_move = find(&mut other, &mg, 56, 48);
+
+    assert!(mg.is_pseudo_legal(&mut board, e4));
+    assert!(!mg.is_pseudo_legal(&mut board, rook_move));
+
+    let heuristics = Heuristics::new();
+    let mut picker = MovePicker::new(
+        &mut board,
+        &mg,
+        Some(rook_move),
+        &heuristics,
+        [None; 2],
+        [None; 2],
+        PIECE_VALUES,
+    );
+    let mut count = 0;
+    while let Some(mv) = picker.next(&mut board, &mg, &heuristics) {
+        assert!(mv != rook_move);
+        count += 1;
+    }
+    assert_eq!(count, 20);
+}
+
+#[test]
+fn test_quiescence_picker_promotions() {
+    let mg = MoveGen;
+    // The rook takes a new queen on a8 for free, yet the promotion is still tried, unlike the
+    // knight that the rook takes just the same
+    let mut board = Board::from_fen("1r2k3/P7/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+    let mut picker = MovePicker::new_quiescence(&mut board, &mg, None, PIECE_VALUES);
+    let mut picked = Vec::new();
+    while let Some(mv) = picker.next(&mut board, &mg, &Heuristics::new()) {
+        picked.push(mv);
+    }
+    let promotes = |piece| picked.iter().any(|mv| mv.to() == 0 && mv.promotion_piece() == piece);
+    assert!(promotes(Pieces::QUEEN));
+    assert!(!promotes(Pieces::KNIGHT));
+}
+
+#[test]
+fn test_history_is_bounded() {
+    let mg = MoveGen;
+    let mut board = Board::from_fen("4k3/8/8/8/8/8/8/R3K3 w - - 0 1").unwrap();
+    let good = find(&mut board, &mg, 56, 48);
+    let bad = find(&mut board, &mg, 56, 57);
+
+    let mut heuristics = Heuristics::new();
+    for _ in 0..1000 {
+        heuristics.update_cutoff(Sides::WHITE, 30, good, [None; 2], &[bad]);
+    }
+
+    assert!(heuristics.history(Sides::WHITE, good) <= MAX_HISTORY);
+    assert!(heuristics.history(Sides::WHITE, good) > MAX_HISTORY / 2);
+    assert!(heuristics.history(Sides::WHITE, bad) >= -MAX_HISTORY);
+    assert!(heuristics.history(Sides::WHITE, bad) < -MAX_HISTORY / 2);
+    assert_eq!(heuristics.history(Sides::BLACK, good), 0);
+}
+
+#[test]
+fn test_clear_resets_heuristics() {
+    let mg = MoveGen;
+    let mut board = Board::from_fen(START_POS).unwrap();
+    let mut search = Search::new(1);
+    search.find_best_move_iter(&mut board, &mg, 5, Duration::MAX);
+
+    let killers =
+        |search: &Search| (0..MAX_DEPTH).any(|ply| search.stack[ply].killers[0].is_some());
+    assert!(killers(&search));
+    assert!(search.heuristics.history.iter().flatten().flatten().any(|&h| h != 0));
+
+    search.clear();
+    let heuristics = &search.heuristics;
+    assert!(!killers(&search));
+    assert!(heuristics.history.iter().flatten().flatten().all(|&h| h == 0));
+    assert!(heuristics.countermoves.iter().flatten().all(Option::is_none));
+    assert!(heuristics.continuation.iter().flatten().flatten().flatten().all(|&h| h == 0));
+    assert!(heuristics.capture_history.iter().flatten().flatten().all(|&h| h == 0));
+}
+
+#[test]
+fn test_see() {
+    let mg = MoveGen;
+    // (fen, from, to, exchange value)
+    let exchanges = [
+        // The rook behind the first one x-rays through it
+        ("4r1k1/8/8/4p3/8/8/4R3/4R1K1 w - - 0 1", 52, 28, 100),
+        ("4r1k1/8/8/4p3/8/8/4R3/6K1 w - - 0 1", 52, 28, -400),
+        // Queen takes a pawn defended by a pawn
+        ("4k3/8/3p4/4p3/8/8/8/4QK2 w - - 0 1", 60, 28, -800),
+        ("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1", 28, 19, 100),
+        // The king can't recapture a defended piece
+        ("4k3/4p3/8/8/8/8/4R3/4R1K1 w - - 0 1", 52, 12, 100),
+        ("4k3/4p3/8/8/8/8/4R3/6K1 w - - 0 1", 52, 12, -400),
+        // Quiet move onto a square covered by a pawn
+        ("4k3/8/3p4/8/8/8/8/R3K3 w - - 0 1", 56, 32, 0),
+        ("4k3/8/3p4/8/8/8/8/4R1K1 w - - 0 1", 60, 28, -500),
+    ];
+
+    for (fen, from, to, value) in exchanges {
+        let mut board = Board::from_fen(fen).unwrap();
+        let mv = find(&mut board, &mg, from, to);
+        assert!(see_ge(&mg, &board, mv, value, &PIECE_VALUES), "{} {}", fen, value);
+        assert!(!see_ge(&mg, &board, mv, value + 1, &PIECE_VALUES), "{} {}", fen, value + 1);
+    }
+
+    // Promotions win the new piece, even onto a square the rook only trades on
+    let promotions = [
+        ("4k3/P7/8/8/8/8/8/4K3 w - - 0 1", 800),
+        ("1r2k3/P7/8/8/8/8/8/R3K3 w - - 0 1", 400),
+        ("1r2k3/P7/8/8/8/8/8/4K3 w - - 0 1", -100),
+    ];
+    for (fen, value) in promotions {
+        let mut board = Board::from_fen(fen).unwrap();
+        let moves = mg.gen_legal_moves(&mut board);
+        let mv = *moves
+            .iter()
+            .find(|mv| mv.to() == 0 && mv.promotion_piece() == Pieces::QUEEN)
+            .unwrap();
+        assert!(see_ge(&mg, &board, mv, value, &PIECE_VALUES), "{} {}", fen, value);
+        assert!(!see_ge(&mg, &board, mv, value + 1, &PIECE_VALUES), "{} {}", fen, value + 1);
+    }
+
+    // The evaluation's values are used, a dearer queen loses more for the pawn
+    let mut board = Board::from_fen("4k3/8/3p4/4p3/8/8/8/4QK2 w - - 0 1").unwrap();
+    let mv = find(&mut board, &mg, 60, 28);
+    let mut values = PIECE_VALUES;
+    values[Pieces::QUEEN] = 1000;
+    assert!(see_ge(&mg, &board, mv, -900, &values));
+    assert!(!see_ge(&mg, &board, mv, -899, &values));
+}
+
+#[test]
+fn test_aspiration_windows() {
+    let mg = MoveGen;
+    let mut board = Board::from_fen(START_POS).unwrap();
+    let mut search = Search::new(1);
+
+    let reports = Arc::new(Mutex::new(Vec::new()));
+    let sink = Arc::clone(&reports);
+    search.on_info = Some(Box::new(move |info| {
+        sink.lock().unwrap().push((info.depth, info.score, info.bound));
+    }));
+    search.find_best_move_iter(&mut board, &mg, 7, Duration::MAX);
+
+    // Failed windows are reported as bounds, but every depth ends with an exact score
+    let reports = reports.lock().unwrap();
+    for depth in 1..=7 {
+        let last = reports.iter().rfind(|report| report.0 == depth).unwrap();
+        assert_eq!(last.2, ScoreBound::Exact);
+        assert_eq!(
+            reports
+                .iter()
+                .filter(|report| report.0 == depth && report.2 == ScoreBound::Exact)
+                .count(),
+            1
+        );
+    }
+    assert_eq!(search.score, Some(reports.last().unwrap().1));
+}
+
+#[test]
+fn test_aborted_search() {
+    let mg = MoveGen;
+    let mut board = Board::from_fen(KIWIPETE).unwrap();
+    let mut search = Search::new(1);
+    let reports = Arc::new(Mutex::new(0));
+    let sink = Arc::clone(&reports);
+    search.on_info = Some(Box::new(move |_| *sink.lock().unwrap() += 1));
+
+    // Stopped before the first node, the fallback move is still legal
+    search.stop.store(true, Ordering::Relaxed);
+    let best_move = search.find_best_move_iter(&mut board, &mg, 10, Duration::MAX).unwrap();
+    assert!(search.stopped);
+    assert!(mg.gen_legal_moves(&mut board).iter().any(|&mv| mv == best_move));
+    assert_eq!(*reports.lock().unwrap(), 0);
+    assert!(search.transposition_table.get(board.zobrist_hash()).is_none());
+
+    // Running out of time keeps the move of a completed iteration
+    search.stop.store(false, Ordering::Relaxed);
+    let best_move = search
+        .find_best_move_iter(&mut board, &mg, 64, Duration::from_millis(200))
+        .unwrap();
+    assert!(search.stopped);
+    assert!(*reports.lock().unwrap() > 0);
+    assert!(mg.gen_legal_moves(&mut board).iter().any(|&mv| mv == best_move));
+}
+
+#[test]
+fn test_lazy_smp() {
+    let mg = MoveGen;
+    let mut board = Board::from_fen(KIWIPETE).unwrap();
+    let mut search = Search::new(1);
+    search.set_threads(3);
+    assert_eq!(search.threads(), 3);
+    let reported = Arc::new(Mutex::new(0));
+    let sink = Arc::clone(&reported);
+    search.on_info = Some(Box::new(move |info| *sink.lock().unwrap() = info.nodes));
+
+    let best_move = search.find_best_move_iter(&mut board, &mg, 5, Duration::MAX).unwrap();
+    assert!(mg.gen_legal_moves(&mut board).iter().any(|&mv| mv == best_move));
+    assert_eq!(search.completed_depth, 5);
+    // Helpers searched too, and everything went into the one shared table
+    assert!(search.total_nodes() > search.nodes);
+    assert!(search.transposition_table.get(board.zobrist_hash()).is_some());
+    // Reports count the helpers' nodes while they are still searching
+    let nodes = *reported.lock().unwrap();
+    assert!(nodes > search.nodes && nodes <= search.total_nodes(), "{}", nodes);
+
+    // Exact root entries from deeper helpers don't stand in for this thread's own search
+    search.find_best_move_iter(&mut board, &mg, 5, Duration::MAX).unwrap();
+    assert_eq!(search.completed_depth, 5);
+    assert!(search.nodes > 100, "{}", search.nodes);
+
+    // A resized table is shared again, and fewer threads still search
+    search.set_table_size(2);
+    search.set_threads(1);
+    assert_eq!(search.threads(), 1);
+    let best_move = search.find_best_move_iter(&mut board, &mg, 3, Duration::MAX).unwrap();
+    assert!(mg.gen_legal_moves(&mut board).iter().any(|&mv| mv == best_move));
+    assert_eq!(search.total_nodes(), search.nodes);
+    assert_eq!(*reported.lock().unwrap(), search.nodes);
+}
+
+#[test]
+fn test_transposition_table() {
+    let mg = MoveGen;
+    let mut board = Board::from_fen(KIWIPETE).unwrap();
+    let table = TranspositionTable::new(1);
+    assert_eq!(table.hashfull(), 0);
+
+    // Every kind of move survives the trip through its compact form
+    for mv in mg.gen_legal_moves(&mut board).iter() {
+        let hash = board.zobrist_hash() ^ mv.0 as u64;
+        table.insert(TranspositionEntry::new(hash, Some(*mv), -INF + 3, -250, 7, MoveType::Minimum));
+        let entry = table.get(hash).unwrap();
+        assert!(entry.best_move(&board) == Some(*mv));
+        assert_eq!(entry.eval, -INF + 3);
+        assert_eq!(entry.static_eval, -250);
+        assert_eq!(entry.depth, 7);
+        assert!(entry.move_type == MoveType::Minimum);
+    }
+
+    // Shallow bounds don't push out a deep entry of the same search, new searches do
+    let hash = board.zobrist_hash();
+    let mv = mg.gen_legal_moves(&mut board).moves[0];
+    table.insert(TranspositionEntry::new(hash, Some(mv), 50, 20, 12, MoveType::Exact));
+    table.insert(TranspositionEntry::new(hash, Some(mv), 10, 20, 2, MoveType::Maximum));
+    assert_eq!(table.get(hash).unwrap().depth, 12);
+    table.new_search();
+    table.insert(TranspositionEntry::new(hash, Some(mv), 10, 20, 2, MoveType::Maximum));
+    assert_eq!(table.get(hash).unwrap().depth, 2);
+
+    // Replaced by an entry without a move, the hash move is kept
+    table.insert(TranspositionEntry::new(hash, None, 30, 30, 0, MoveType::Minimum));
+    let entry = table.get(hash).unwrap();
+    assert_eq!((entry.depth, entry.eval), (0, 30));
+    assert!(entry.best_move(&board) == Some(mv));
+
+    // In a single bucket a position keeps its slot, and a new one pushes out the shallowest
+    let table = TranspositionTable::new(0);
+    for (key, depth) in [(1, 10), (2, 3), (3, 8), (4, 6), (1, 12)] {
+        table.insert(TranspositionEntry::new(key, Some(mv), 0, 0, depth, MoveType::Exact));
+    }
+    assert!((1..=4).all(|key| table.get(key).is_some()));
+    assert_eq!(table.get(1).unwrap().depth, 12);
+    table.insert(TranspositionEntry::new(5, Some(mv), 0, 0, 9, MoveType::Exact));
+    assert!(table.get(2).is_none());
+    assert!([1, 3, 4, 5].iter().all(|&key| table.get(key).is_some()));
+
+    // Filling the table from a search shows up in hashfull
+    let mut search = Search::new(1);
+    search.find_best_move_iter(&mut board, &mg, 6, Duration::MAX);
+    assert!(search.transposition_table.hashfull() > 0);
+    search.clear();
+    assert_eq!(search.transposition_table.hashfull(), 0);
+}
+
+#[test]
+fn test_root_ignores_table_cutoff() {
+    let mg = MoveGen;
+    let fen = "r1bqkb1r/pppp1ppp/2n2n2/4p3/4P3/2N2N2/PPPP1PPP/R1BQKB1R w KQkq - 4 4";
+    let mut board = Board::from_fen(fen).unwrap();
+    let mut search = Search::new(16);
+
+    // The second search finds exact root entries at every depth, but still searches the moves
+    let first = search.find_best_move_iter(&mut board, &mg, 5, Duration::MAX);
+    let second = search.find_best_move_iter(&mut board, &mg, 5, Duration::MAX);
+    assert_eq!(first, second);
+    assert!(search.nodes > 100, "{}", search.nodes);
+    assert_eq!(search.completed_depth, 5);
+}
+
+#[test]
+fn test_extensions() {
+    let count_nodes = |params: &SearchParams| search_fen(KIWIPETE, 6, params).2;
+
+    let mut disabled = SearchParams {
+        singular_min_depth: 4,
+        ..SearchParams::default()
+    };
+    for (_, enabled) in disabled.toggles() {
+        *enabled = false;
+    }
+    let baseline = count_nodes(&disabled);
+
+    // Each extension on its own changes the tree
+    for i in 0..4 {
+        let mut params = disabled.clone();
+        *params.toggles()[i].1 = true;
+        assert_ne!(count_nodes(&params), baseline, "{}", params.toggles()[i].0);
+    }
+
+    // Without a budget no extension is ever made
+    let no_budget = SearchParams {
+        extension_budget: 0,
+        singular_min_depth: 4,
+        ..SearchParams::default()
+    };
+    assert_eq!(count_nodes(&no_budget), baseline);
+}
+
+#[test]
+fn test_shallow_pruning() {
+    let disabled = SearchParams {
+        razor_max_depth: 0,
+        futility_max_depth: 0,
+        lmp_max_depth: 0,
+        history_prune_max_depth: 0,
+        ..SearchParams::default()
+    };
+
+    let (_, _, pruned) = search_fen(KIWIPETE, 6, &SearchParams::default());
+    let (_, _, full) = search_fen(KIWIPETE, 6, &disabled);
+    assert!(pruned < full, "{} {}", pruned, full);
+
+    // Pruning never hides a forced mate
+    assert_back_rank_mate(4, &SearchParams::default());
+    assert_back_rank_mate(4, &disabled);
+}
+
+#[test]
+fn test_quiescence() {
+    let mg = MoveGen;
+    let mut search = Search::new(1);
+
+    // Checkmated, the score counts the plies from the root
+    let mut mated = Board::from_fen("R5k1/5ppp/8/8/8/8/8/6K1 b - - 0 1").unwrap();
+    assert_eq!(search.quiesce(&mut mated, &mg, -INF, INF, 3), -INF + 3);
+    let entry = search.transposition_table.get(mated.zobrist_hash()).unwrap();
+    assert_eq!(entry.depth, 0);
+
+    // In check there is no standing pat, every king move loses the rook to the fork
+    let mut board = Board::from_fen("7k/7p/8/8/8/8/2n5/R3K3 w - - 0 1").unwrap();
+    let score = search.quiesce(&mut board, &mg, -INF, INF, 0);
+    assert!(score < 0, "{}", score);
+
+    // Out of plies in check, the position still gets its evaluation instead of a draw
+    let mut board = Board::from_fen("4k3/8/8/8/8/8/4r3/r3K3 w - - 0 1").unwrap();
+    let score = search.quiesce(&mut board, &mg, -INF, INF, MAX_DEPTH - 1);
+    assert!(score < -500, "{}", score);
+
+    // A mate found through the table keeps its distance from the root
+    let mut board = Board::from_fen(BACK_RANK_MATE).unwrap();
+    for _ in 0..2 {
+        search.find_best_move_iter(&mut board, &mg, 5, Duration::MAX);
+        assert_eq!(search.score, Some(INF - 3));
+    }
+}
+
+#[test]
+fn test_tunables() {
+    let mut search = Search::new(1);
+
+    let choices = search.params.choices();
+    assert_eq!(choices[0].0, "InternalIteration");
+    assert_eq!(choices[0].1[*choices[0].2], "Reductions");
+
+    // A mode isn't a number SPSA could tune, so it's no tunable either
+    let mut names: Vec<&str> = vec!["InternalIteration"];
+    for tunable in search.tunables() {
+        assert!((tunable.min..=tunable.max).contains(&tunable.get()), "{}", tunable.name);
+        assert!(tunable.step > 0, "{}", tunable.name);
+        assert!(!names.contains(&tunable.name), "{}", tunable.name);
+        names.push(tunable.name);
+    }
+
+    let line = |search: &mut Search, name: &str| {
+        let tunables = search.tunables();
+        tunables.iter().find(|t| t.name == name).unwrap().spsa_line()
+    };
+    assert_eq!(line(&mut search, "LmrBase"), "LmrBase, int, 278, 0, 500, 20, 0.002");
+
+    // Values are written through to the parameters, unless out of range
+    let set = |search: &mut Search, name: &str, value: i32| {
+        let mut tunables = search.tunables();
+        tunables.iter_mut().find(|t| t.name == name).unwrap().set(value)
+    };
+    assert!(set(&mut search, "NullMoveReduction", 4));
+    assert_eq!(search.params.null_move_reduction, 4);
+    assert!(!set(&mut search, "NullMoveReduction", 0));
+    assert_eq!(search.params.null_move_reduction, 4);
+
+    // Material comes from the evaluation parameters
+    let mut board = Board::from_fen("4k3/8/8/8/8/8/4P3/3NK3 w - - 0 1").unwrap();
+    let before = search.static_eval(&mut board);
+    assert!(set(&mut search, "KnightValue", 350));
+    assert_eq!(search.static_eval(&mut board), before + 50);
+    assert_eq!(search.eval_breakdown(&board).material, 450);
+}
+
+#[test]
+fn test_lmr_table() {
+    let mut search = Search::new(1);
+    assert_eq!(search.reduction(1, 1), 2);
+    for depth in 1..64 {
+        for move_count in 1..64 {
+            let reduction = search.reduction(depth, move_count);
+            assert!(reduction >= search.reduction(depth - 1, move_count));
+            assert!(reduction >= search.reduction(depth, move_count - 1));
+        }
+    }
+    // Past the end of the table the last entry is used
+    assert_eq!(search.reduction(200, 200), search.reduction(63, 63));
+
+    // Built from the parameters
+    let before = search.reduction(10, 10);
+    search.params.lmr_factor += 50;
+    search.init_lmr_table();
+    assert!(search.reduction(10, 10) > before);
+}
+
+#[test]
+fn test_search_stack() {
+    let mg = MoveGen;
+    let mut board = Board::from_fen(START_POS).unwrap();
+    let e4 = find(&mut board, &mg, 52, 36);
+    let d4 = find(&mut board, &mg, 51, 35);
+
+    let mut stack = SearchStack::new();
+    stack[0].current_move = Some(e4);
+    assert_eq!(stack.previous_move(0), None);
+    assert_eq!(stack.previous_move(1), Some(e4));
+
+    // Killers are kept per ply, newest first
+    stack.update_killers(3, e4);
+    stack.update_killers(3, d4);
+    stack.update_killers(3, d4);
+    assert_eq!(stack[3].killers, [Some(d4), Some(e4)]);
+    assert_eq!(stack.is_killer(3, e4), Some(1));
+    assert_eq!(stack.is_killer(2, e4), None);
+
+    // Compared with two plies back, or four when that was in check
+    stack[0].static_eval = 50;
+    stack[2].static_eval = 20;
+    assert!(!stack.improving(2));
+    stack[2].static_eval = 80;
+    assert!(stack.improving(2));
+    stack[4].static_eval = 60;
+    stack[2].in_check = true;
+    assert!(stack.improving(4));
+    stack[0].static_eval = 70;
+    assert!(!stack.improving(4));
+
+    // Never while in check, always without anything to compare to
+    stack[4].in_check = true;
+    assert!(!stack.improving(4));
+    assert!(stack.improving(1));
+}
+
+#[test]
+fn test_internal_iteration() {
+    let params = |mode: usize| SearchParams {
+        internal_iteration: mode,
+        // Low enough to reach PV nodes the previous iterations left without a hash move
+        iid_min_depth: 3,
+        ..SearchParams::default()
+    };
+
+    // Each mode changes the tree
+    let (_, _, off) = search_fen(KIWIPETE, 7, &params(INTERNAL_OFF));
+    let (_, _, reductions) = search_fen(KIWIPETE, 7, &params(INTERNAL_REDUCTIONS));
+    let (_, _, deepening) = search_fen(KIWIPETE, 7, &params(INTERNAL_DEEPENING));
+    assert_ne!(off, reductions);
+    assert_ne!(off, deepening);
+
+    // And still finds the mate
+    for mode in [INTERNAL_OFF, INTERNAL_REDUCTIONS, INTERNAL_DEEPENING] {
+        assert_back_rank_mate(7, &params(mode));
+    }
+}
+
+#[test]
+fn test_continuation_and_capture_history() {
+    let mg = MoveGen;
+    let mut board = Board::from_fen(START_POS).unwrap();
+    let e4 = find(&mut board, &mg, 52, 36);
+    let d4 = find(&mut board, &mg, 51, 35);
+    board.do_move(&e4);
+    let e5 = find(&mut board, &mg, 12, 28);
+    let d5 = find(&mut board, &mg, 11, 27);
+
+    // Only after the same earlier move
+    let mut heuristics = Heuristics::new();
+    heuristics.update_cutoff(Sides::BLACK, 10, e5, [Some(e4), None], &[d5]);
+    let after_e4 = heuristics.continuation(Sides::BLACK, [Some(e4), None], e5);
+    assert!(after_e4 > 0);
+    assert!(heuristics.continuation(Sides::BLACK, [Some(e4), None], d5) < 0);
+    assert_eq!(heuristics.continuation(Sides::BLACK, [Some(d4), None], e5), 0);
+    assert_eq!(heuristics.continuation(Sides::BLACK, [None, None], e5), 0);
+    // Moves two plies back are our own, a black pawn to e4 is another entry than white's
+    assert_eq!(heuristics.continuation(Sides::BLACK, [None, Some(e4)], e5), 0);
+    // And white playing the same moves shares nothing with black
+    assert_eq!(heuristics.continuation(Sides::WHITE, [Some(e4), None], e5), 0);
+    let butterfly = heuristics.history(Sides::BLACK, e5);
+    let combined = heuristics.quiet_history(Sides::BLACK, e5, [Some(e4), Some(e4)]);
+    assert_eq!(combined, butterfly + after_e4);
+
+    // Capture history picks between equal captures, but never beats a bigger victim
+    let mut board = Board::from_fen("4k3/8/8/2p1p3/3P4/5Q1q/8/4K3 w - - 0 1").unwrap();
+    let dxc5 = find(&mut board, &mg, 35, 26);
+    let dxe5 = find(&mut board, &mg, 35, 28);
+    let qxh3 = find(&mut board, &mg, 45, 47);
+    for _ in 0..100 {
+        heuristics.update_captures(30, Some(dxe5), &[dxc5, qxh3]);
+    }
+    assert!(score_capture(dxe5, &heuristics) > score_capture(dxc5, &heuristics));
+    assert!(score_capture(qxh3, &heuristics) > score_capture(dxe5, &heuristics));
+}
+
+#[test]
+fn test_fifty_move_rule() {
+    let score = |fen: &str| search_fen(fen, 4, &SearchParams::default()).1;
+
+    // Every move ends the game drawn, except the mate
+    assert_eq!(score("7k/8/6K1/8/8/8/8/5Q2 w - - 99 1"), INF - 1);
+    assert_eq!(score("7k/8/8/8/8/8/8/K4Q2 w - - 99 1"), 0);
+    assert!(score("7k/8/8/8/8/8/8/K4Q2 w - - 0 1") > 500);
+}
+
+#[test]
+fn test_endgame_scale() {
+    let mg = MoveGen;
+    let search = Search::new(1);
+    let scale = |fen: &str| search.eval_breakdown(&Board::from_fen(fen).unwrap()).scale;
+
+    assert_eq!(scale(START_POS), SCALE_NORMAL);
+    // Dead draws and a lone minor piece
+    assert_eq!(scale("4k3/8/8/8/8/8/8/2B1KB2 w - - 0 1"), SCALE_NORMAL);
+    assert_eq!(scale("2b1k3/8/8/8/8/8/8/4KB2 w - - 0 1"), 0);
+    assert_eq!(scale("4kn2/8/8/8/8/8/8/4KB2 w - - 0 1"), 0);
+    assert_eq!(scale("4kn2/8/8/8/8/8/8/4KR2 w - - 0 1"), SCALE_NORMAL / 8);
+    // Wrong bishop for the h-pawn, unless the king is away from the corner
+    assert_eq!(scale("7k/8/8/8/8/7P/8/3BK3 w - - 0 1"), 0);
+    assert_eq!(scale("8/8/8/3k4/8/7P/8/3BK3 w - - 0 1"), SCALE_NORMAL);
+    assert_eq!(scale("7k/8/8/8/8/7P/8/2B1K3 w - - 0 1"), SCALE_NORMAL);
+    // Black's a-pawn promotes on a1, a dark square
+    assert_eq!(scale("4k3/8/8/8/8/p7/8/1K1b4 b - - 0 1"), 0);
+    // Opposite colored bishops, and even rook endings
+    assert_eq!(scale("4kb2/4p3/8/8/8/8/3PP3/4KB2 w - - 0 1"), SCALE_NORMAL / 2);
+    assert_eq!(scale("r3kb2/4p3/8/8/8/8/3PP3/R3KB2 w - - 0 1"), SCALE_NORMAL * 3 / 4);
+    assert_eq!(scale("r3k3/4p3/8/8/8/8/3PP3/R3K3 w - - 0 1"), SCALE_NORMAL / 2);
+    assert_eq!(scale("r3k3/8/8/8/8/8/3PP3/R3K3 w - - 0 1"), SCALE_NORMAL);
+
+    // The search scores dead positions as draws
+    let mut board = Board::from_fen("4k3/8/8/8/8/8/3n4/4KR2 w - - 0 1").unwrap();
+    let mut search = Search::new(1);
+    search.find_best_move_iter(&mut board, &mg, 4, Duration::MAX);
+    assert!(search.score.unwrap() > 0);
+    let mut board = Board::from_fen("4k3/8/8/8/8/8/3b4/4KN2 w - - 0 1").unwrap();
+    search.find_best_move_iter(&mut board, &mg, 4, Duration::MAX);
+    assert_eq!(search.score, Some(0));
+}
@@ -1,14 +1,14 @@
 use core::{
     board::{
         board::Board,
-        defs::{Bitboard, Pieces, Sides, Square, START_POS},
+        defs::{Bitboard, GameResult, Pieces, Sides, Square, START_POS},
     },
     movegen::{
-        movegen::{bitscan_forward, MoveGen}, movelist::MoveList, moves::Move
+        movegen::MoveGen, movelist::MoveList
     },
-    search::{search::Search, ttable::TranspositionTable},
+    search::search::Search,
 };
-use std::time::{Duration, Instant};
+use std::time::Duration;
 
 use macroquad::{
     color::{Color, WHITE},
@@ -16,6 +16,7 @@ use macroquad::{
     input::{is_mouse_button_down, mouse_position, MouseButton},
     math::vec2,
     shapes::{draw_circle, draw_rectangle},
+    text::{draw_text, measure_text},
     texture::{draw_texture_ex, load_texture, DrawTextureParams, Texture2D},
     window::{next_frame, screen_height, screen_width, Conf},
 };
@@ -63,6 +64,20 @@ fn window_conf() -> Conf {
     }
 }
 
+// The result and its reason across the middle of the board
+fn draw_result(result: &GameResult, board_size: f32) {
+    let banner = Color::from_rgba(0, 0, 0, 180);
+    draw_rectangle(0.0, 0.35 * board_size, board_size, 0.3 * board_size, banner);
+
+    for (text, font_size, y) in [
+        (result.pgn(), board_size / 10.0, 0.48),
+        (result.reason(), board_size / 18.0, 0.58),
+    ] {
+        let dimensions = measure_text(text, None, font_size as u16, 1.0);
+        draw_text(text, (board_size - dimensions.width) / 2.0, y * board_size, font_size, WHITE);
+    }
+}
+
 fn draw_bitboard_pieces(bitboard: Bitboard, texture: &Texture2D, square_size: f32) {
     // A common pattern: iterate from 0..64 for each bit in the u64
     // If the bit is set, draw that piece
@@ -94,18 +109,14 @@ async fn main() {
 
     let mg = MoveGen;
     let mut board = Board::from_fen(START_POS).expect("Invalid FEN");
-    let mut search = Search {
-        transposition_table: TranspositionTable::new(2000000),
-        best_move: None,
-        psqt_cache: Box::new([[[0; 64]; 6]; 257]),
-    };
-
-    search.init_psqt_cache();
+    let mut search = Search::new(64);
 
     //let mut board = Board::from_fen("3R4/3R4/3K4/8/8/8/3k4/8 b - - 0 1").expect("Invalid FEN");
     //let mut board = Board::from_fen("8/8/1Kpp4/1P5r/1R3p1k/4P3/6P1/8 b - - 1 2").unwrap();
 
     let mut moves = mg.gen_legal_moves(&mut board);
+    // Once set the board only shows the final position
+    let mut result = board.game_result(&mg);
     let mut move_hints: Vec<Square> = vec![];
     let mut active_square: Option<Square> = None;
 
@@ -168,7 +179,7 @@ async fn main() {
             draw_bitboard_pieces(bitboard, texture, square_size);
         }
 
-        if let Some(square_index) = active_square {
+        if let Some(square_index) = active_square.filter(|_| result.is_none()) {
             // Create moves that are only on the from square
             let mut moves_from = MoveList::new();
 
@@ -181,10 +192,10 @@ async fn main() {
             move_hints.clear();
             for _move in moves_from.iter() {
                 if _move.from() == square_index {
-                    move_hints.push(_move.to());
+                    move_hints.push(_move.destination());
 
-                    let x = square_size * (_move.to() % 8) as f32;
-                    let y = square_size * (_move.to() / 8) as f32;
+                    let x = square_size * (_move.destination() % 8) as f32;
+                    let y = square_size * (_move.destination() / 8) as f32;
 
                     draw_circle(
                         x + 0.5 * square_size,
@@ -211,8 +222,12 @@ async fn main() {
             );
         }
 
+        if let Some(result) = &result {
+            draw_result(result, board_size);
+        }
+
         // TODO: Add mouse right deselect
-        if is_mouse_button_down(MouseButton::Left) {
+        if result.is_none() && is_mouse_button_down(MouseButton::Left) {
             let pos = mouse_position();
             let x = pos.0 / square_size;
             let y = pos.1 / square_size;
@@ -225,22 +240,31 @@ async fn main() {
                 if move_hints.contains(&target) {
                     for _move in moves.clone().iter() {
                         if _move.from() == active_square.expect("Corrupted board state")
-                            && _move.to() == target
+                            && _move.destination() == target
                         {
                             // Force promote to queen
                             if _move.is_promotion() && _move.promotion_piece() != Pieces::QUEEN {
                                 continue;
                             }
 
-                            board.do_move(&_move);
-
-                            if let Some(best_move) =
-                                search.find_best_move_iter(&mut board, &mg, 20, Duration::from_millis(1000))
-                            {
-                                board.do_move(&best_move);
+                            board.do_move(_move);
+
+                            if board.game_result(&mg).is_none() {
+                                if let Some(best_move) = search.find_best_move_iter(
+                                    &mut board,
+                                    &mg,
+                                    20,
+                                    Duration::from_millis(1000),
+                                ) {
+                                    board.do_move(&best_move);
+                                }
                             }
 
-                            moves = mg.gen_legal_moves_no_rep(&mut board);
+                            result = board.game_result(&mg);
+                            if let Some(result) = &result {
+                                println!("{}", result);
+                            }
+                            moves = mg.gen_legal_moves(&mut board);
                             break;
                         }
                     }
@@ -0,0 +1,444 @@
+use core::{
+    board::{board::Board, defs::START_POS},
+    movegen::movegen::MoveGen,
+    search::{defs::SCALE_NORMAL, params::Tunable, search::Search},
+};
+use std::{
+    io::{BufRead, Write},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::Instant,
+};
+
+use crate::{
+    go::GoCommand,
+    notation::{format_info, move_to_alg},
+    position::{set_position, PositionCommand},
+};
+
+pub const DEFAULT_HASH_MB: usize = 64;
+pub const MAX_HASH_MB: usize = 4096;
+pub const MAX_THREADS: usize = 256;
+
+// Protocol front end. Commands are read line by line and all responses, including the ones
+// produced by the search thread, go to the shared writer.
+pub struct UciEngine<W: Write + Send + 'static> {
+    board: Board,
+    movegen: MoveGen,
+    // Taken by the search thread while a `go` is running
+    search: Option<Search>,
+    search_thread: Option<JoinHandle<Search>>,
+    // Shared with the search, so it can be stopped while the thread owns it
+    stop: Arc<AtomicBool>,
+    // Last successfully applied `position` command
+    position: Option<PositionCommand>,
+    // Keep the hash between moves of a game, otherwise every `go` starts from a clear table
+    keep_hash: bool,
+    // UCI_Chess960, changes how castles are written. Shared with the info callback.
+    chess960: Arc<AtomicBool>,
+    // List the tunable parameters in the `uci` response, they can be set either way
+    show_tunables: bool,
+    output: Arc<Mutex<W>>,
+}
+
+impl<W: Write + Send + 'static> UciEngine<W> {
+    pub fn new(output: W) -> UciEngine<W> {
+        let output = Arc::new(Mutex::new(output));
+        let mut search = Search::new(DEFAULT_HASH_MB);
+        let chess960 = Arc::new(AtomicBool::new(false));
+
+        let info_output = Arc::clone(&output);
+        let info_chess960 = Arc::clone(&chess960);
+        search.on_info = Some(Box::new(move |info| {
+            let line = format_info(info, info_chess960.load(Ordering::Relaxed));
+            let mut out = info_output.lock().unwrap();
+            writeln!(out, "{}", line).ok();
+            out.flush().ok();
+        }));
+
+        UciEngine {
+            board: Board::from_fen(START_POS).unwrap(),
+            movegen: MoveGen,
+            stop: Arc::clone(&search.stop),
+            search: Some(search),
+            search_thread: None,
+            position: None,
+            keep_hash: true,
+            chess960,
+            show_tunables: false,
+            output,
+        }
+    }
+
+    // For tuners that only send options the engine advertised
+    pub fn show_tunables(&mut self) {
+        self.show_tunables = true;
+    }
+
+    pub fn run<R: BufRead>(&mut self, input: R) {
+        for line in input.lines() {
+            let Ok(line) = line else {
+                break;
+            };
+
+            if !self.handle_command(&line) {
+                break;
+            }
+        }
+
+        self.wait_for_search();
+    }
+
+    // Returns false once the engine should exit
+    pub fn handle_command(&mut self, line: &str) -> bool {
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        let Some(&command) = parts.first() else {
+            return true;
+        };
+
+        match command {
+            "uci" => {
+                self.send("id name chess-engine");
+                self.send("id author enriktigasna");
+                self.send(&format!(
+                    "option name Hash type spin default {} min 1 max {}",
+                    DEFAULT_HASH_MB, MAX_HASH_MB
+                ));
+                self.send(&format!(
+                    "option name Threads type spin default 1 min 1 max {}",
+                    MAX_THREADS
+                ));
+                self.send("option name Clear Hash type button");
+                self.send("option name Keep Hash type check default true");
+                self.send("option name UCI_Chess960 type check default false");
+                let toggles: Vec<String> = self
+                    .search()
+                    .params
+                    .toggles()
+                    .into_iter()
+                    .map(|(name, value)| format!("option name {} type check default {}", name, value))
+                    .collect();
+                for toggle in toggles {
+                    self.send(&toggle);
+                }
+                let choices: Vec<String> = self
+                    .search()
+                    .params
+                    .choices()
+                    .into_iter()
+                    .map(|(name, vars, value)| {
+                        let list: String = vars.iter().map(|var| format!(" var {}", var)).collect();
+                        format!("option name {} type combo default {}{}", name, vars[*value], list)
+                    })
+                    .collect();
+                for choice in choices {
+                    self.send(&choice);
+                }
+                if self.show_tunables {
+                    for tunable in self.tunables(|tunable| {
+                        format!(
+                            "option name {} type spin default {} min {} max {}",
+                            tunable.name,
+                            tunable.get(),
+                            tunable.min,
+                            tunable.max
+                        )
+                    }) {
+                        self.send(&tunable);
+                    }
+                }
+                self.send("uciok");
+            }
+            "isready" => self.send("readyok"),
+            "ucinewgame" => {
+                self.search().clear();
+                self.board = Board::from_fen(START_POS).unwrap();
+                self.position = None;
+            }
+            "setoption" => {
+                self.wait_for_search();
+                self.set_option(&parts[1..]);
+            }
+            "position" => {
+                self.wait_for_search();
+                self.set_position(&parts[1..]);
+            }
+            "go" => {
+                self.wait_for_search();
+                self.go(GoCommand::parse(&parts[1..]));
+            }
+            "stop" => self.stop_search(),
+            "info" => {
+                let hash = self.board.zobrist_hash();
+                if let Some(entry) = self.search().transposition_table.get(hash) {
+                    let line = format!("info score {} depth {}", entry.eval, entry.depth);
+                    self.send(&line);
+                }
+            }
+            // Non-standard commands for debugging from a terminal. The ones that need the search
+            // can't wait for it, a `go infinite` only ends with a `stop` read after them.
+            "eval" | "spsa" | "perft" | "flip" if self.searching() => {
+                self.send(&format!("info string {} unavailable while searching", command))
+            }
+            "d" => {
+                let diagram = format!("{}", self.board);
+                self.send(&diagram);
+                self.send(&format!("Fen: {}", self.board.to_fen()));
+                self.send(&format!("Key: {:016X}", self.board.zobrist_hash()));
+            }
+            "eval" => self.eval(),
+            "spsa" => {
+                for line in self.tunables(|tunable| tunable.spsa_line()) {
+                    self.send(&line);
+                }
+            }
+            "perft" => match parts.get(1).and_then(|depth| depth.parse::<usize>().ok()) {
+                Some(depth) if depth > 0 => self.perft(depth),
+                _ => self.send("info string usage: perft <depth>"),
+            },
+            "flip" => {
+                self.wait_for_search();
+                self.board = self.board.mirrored();
+                // The board no longer matches the last `position` command
+                self.position = None;
+            }
+            "quit" => {
+                self.stop_search();
+                return false;
+            }
+            _ => self.send(&format!("info string unknown command '{}'", command)),
+        }
+
+        true
+    }
+
+    // Flushes and hands back the writer, waiting for any running search first
+    pub fn into_output(mut self) -> W {
+        self.wait_for_search();
+        let output = Arc::clone(&self.output);
+        drop(self);
+
+        match Arc::try_unwrap(output) {
+            Ok(output) => output.into_inner().unwrap(),
+            Err(_) => panic!("Search output still shared"),
+        }
+    }
+
+    fn send(&self, line: &str) {
+        let mut out = self.output.lock().unwrap();
+        writeln!(out, "{}", line).ok();
+        out.flush().ok();
+    }
+
+    fn searching(&self) -> bool {
+        self.search_thread
+            .as_ref()
+            .is_some_and(|thread| !thread.is_finished())
+    }
+
+    fn search(&mut self) -> &mut Search {
+        self.wait_for_search();
+        self.search.as_mut().expect("Search missing after join")
+    }
+
+    fn set_position(&mut self, args: &[&str]) {
+        let result = PositionCommand::parse(args).and_then(|command| {
+            set_position(
+                &mut self.board,
+                &self.movegen,
+                self.position.as_ref(),
+                &command,
+                self.chess960.load(Ordering::Relaxed),
+            )
+            .map(|_| command)
+        });
+
+        match result {
+            Ok(command) => self.position = Some(command),
+            Err(err) => self.send(&format!("info string {}", err)),
+        }
+    }
+
+    fn set_option(&mut self, args: &[&str]) {
+        // setoption name <id> [value <x>], where the id may contain spaces
+        let value_index = args.iter().position(|&p| p == "value");
+        let name = args[..value_index.unwrap_or(args.len())]
+            .iter()
+            .skip_while(|&&p| p == "name")
+            .copied()
+            .collect::<Vec<&str>>()
+            .join(" ");
+        let value = value_index.map(|i| args[i + 1..].join(" "));
+
+        match (name.to_lowercase().as_str(), value) {
+            ("hash", Some(value)) => match value.parse::<usize>() {
+                Ok(mb) if (1..=MAX_HASH_MB).contains(&mb) => {
+                    self.search().set_table_size(mb);
+                }
+                _ => self.send(&format!("info string invalid Hash value '{}'", value)),
+            },
+            ("threads", Some(value)) => match value.parse::<usize>() {
+                Ok(threads) if (1..=MAX_THREADS).contains(&threads) => {
+                    self.search().set_threads(threads);
+                }
+                _ => self.send(&format!("info string invalid Threads value '{}'", value)),
+            },
+            ("clear hash", None) => self.search().clear(),
+            ("keep hash", Some(value)) => match value.as_str() {
+                "true" => self.keep_hash = true,
+                "false" => self.keep_hash = false,
+                _ => self.send(&format!("info string invalid Keep Hash value '{}'", value)),
+            },
+            ("uci_chess960", Some(value)) => match value.as_str() {
+                "true" => self.chess960.store(true, Ordering::Relaxed),
+                "false" => self.chess960.store(false, Ordering::Relaxed),
+                _ => self.send(&format!("info string invalid UCI_Chess960 value '{}'", value)),
+            },
+            (option, value) => self.set_param(&name, option, value),
+        }
+    }
+
+    // Search feature switches, choices and tunable parameters, see SearchParams::toggles,
+    // SearchParams::choices and Search::tunables
+    fn set_param(&mut self, name: &str, option: &str, value: Option<String>) {
+        let search = self.search();
+        let toggle = search
+            .params
+            .toggles()
+            .into_iter()
+            .find(|(toggle, _)| toggle.to_lowercase() == option);
+
+        let valid = match (toggle, value.as_deref()) {
+            (Some((_, enabled)), Some("true")) => {
+                *enabled = true;
+                true
+            }
+            (Some((_, enabled)), Some("false")) => {
+                *enabled = false;
+                true
+            }
+            (Some(_), _) => false,
+            (None, _) => {
+                let choice = search
+                    .params
+                    .choices()
+                    .into_iter()
+                    .find(|(choice, _, _)| choice.to_lowercase() == option);
+                if let Some((_, vars, selected)) = choice {
+                    let value = value.as_deref().unwrap_or_default().to_lowercase();
+                    let index = vars.iter().position(|var| var.to_lowercase() == value);
+                    if let Some(index) = index {
+                        *selected = index;
+                    }
+                    index.is_some()
+                } else {
+                    let tunable = search
+                        .tunables()
+                        .into_iter()
+                        .find(|tunable| tunable.name.to_lowercase() == option);
+                    let Some(mut tunable) = tunable else {
+                        self.send(&format!("info string unknown option '{}'", name));
+                        return;
+                    };
+                    value
+                        .as_deref()
+                        .and_then(|value| value.parse::<i32>().ok())
+                        .is_some_and(|value| tunable.set(value))
+                }
+            }
+        };
+
+        if !valid {
+            let value = value.unwrap_or_default();
+            self.send(&format!("info string invalid {} value '{}'", name, value));
+        }
+    }
+
+    // Formats every tunable parameter
+    fn tunables(&mut self, format: impl Fn(&Tunable) -> String) -> Vec<String> {
+        self.search().tunables().iter().map(format).collect()
+    }
+
+    fn eval(&mut self) {
+        let mut board = self.board.clone();
+        let search = self.search();
+        let breakdown = search.eval_breakdown(&board);
+        let final_eval = search.static_eval(&mut board);
+
+        self.send("Term          | White");
+        self.send("--------------+-------");
+        for (term, value) in [
+            ("Material", breakdown.material),
+            ("Piece-square", breakdown.psqt),
+            ("Doubled pawns", breakdown.double_pawns),
+            ("Total", breakdown.total),
+        ] {
+            self.send(&format!("{:<13} | {:>5}", term, value));
+        }
+        self.send(&format!("Phase: {}/256", breakdown.phase));
+        self.send(&format!("Scale: {}/{}", breakdown.scale, SCALE_NORMAL));
+        self.send(&format!("Final evaluation: {} (side to move)", final_eval));
+    }
+
+    fn perft(&mut self, depth: usize) {
+        self.wait_for_search();
+        let start = Instant::now();
+        let moves = self.movegen.gen_legal_moves(&mut self.board);
+
+        let chess960 = self.chess960.load(Ordering::Relaxed);
+        let mut total = 0;
+        for mv in moves.iter() {
+            self.board.do_move(mv);
+            let count = self.movegen.perft(&mut self.board, depth - 1);
+            self.board.undo_move(mv);
+
+            total += count;
+            self.send(&format!("{}: {}", move_to_alg(mv, chess960), count));
+        }
+
+        self.send("");
+        self.send(&format!("Nodes searched: {}", total));
+        self.send(&format!("Time: {} ms", start.elapsed().as_millis()));
+    }
+
+    fn go(&mut self, command: GoCommand) {
+        let mut search = self.search.take().expect("Search missing after join");
+        if !self.keep_hash {
+            search.clear();
+        }
+        let mut board = self.board.clone();
+        let output = Arc::clone(&self.output);
+        let (max_depth, duration) = command.limits(board.us());
+        let chess960 = self.chess960.load(Ordering::Relaxed);
+
+        self.stop.store(false, Ordering::Relaxed);
+        self.search_thread = Some(thread::spawn(move || {
+            let best_move = search.find_best_move_iter(&mut board, &MoveGen, max_depth, duration);
+            let line = match best_move {
+                Some(mv) => format!("bestmove {}", move_to_alg(&mv, chess960)),
+                None => "bestmove 0000".to_string(),
+            };
+
+            let mut out = output.lock().unwrap();
+            writeln!(out, "{}", line).ok();
+            out.flush().ok();
+            drop(out);
+
+            search
+        }));
+    }
+
+    fn stop_search(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        self.wait_for_search();
+    }
+
+    fn wait_for_search(&mut self) {
+        if let Some(thread) = self.search_thread.take() {
+            self.search = Some(thread.join().expect("Search thread panicked"));
+        }
+    }
+}
@@ -0,0 +1,77 @@
+use core::{board::defs::Sides, search::defs::MAX_DEPTH};
+use std::time::Duration;
+
+// Longest we'll think on a single move when playing on a clock
+const MAX_MOVE_TIME: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct GoCommand {
+    pub depth: Option<usize>,
+    pub movetime: Option<u64>,
+    pub wtime: Option<u64>,
+    pub btime: Option<u64>,
+    pub winc: Option<u64>,
+    pub binc: Option<u64>,
+    pub infinite: bool,
+}
+
+impl GoCommand {
+    // Parses the arguments following "go", unknown or malformed values are ignored
+    pub fn parse(args: &[&str]) -> GoCommand {
+        let mut command = GoCommand::default();
+        let mut tokens = args.iter();
+
+        while let Some(&token) = tokens.next() {
+            if token == "infinite" {
+                command.infinite = true;
+                continue;
+            }
+
+            let value = match tokens.clone().next().and_then(|v| v.parse::<u64>().ok()) {
+                Some(value) => value,
+                None => continue,
+            };
+
+            match token {
+                "depth" => command.depth = Some(value as usize),
+                "movetime" => command.movetime = Some(value),
+                "wtime" => command.wtime = Some(value),
+                "btime" => command.btime = Some(value),
+                "winc" => command.winc = Some(value),
+                "binc" => command.binc = Some(value),
+                _ => continue,
+            }
+            tokens.next();
+        }
+
+        command
+    }
+
+    // Maximum depth and thinking time for the side to move
+    pub fn limits(&self, side: usize) -> (usize, Duration) {
+        let depth = self.depth.unwrap_or(MAX_DEPTH).clamp(1, MAX_DEPTH);
+
+        if self.infinite {
+            return (depth, Duration::MAX);
+        }
+
+        if let Some(movetime) = self.movetime {
+            return (depth, Duration::from_millis(movetime));
+        }
+
+        let (time, inc) = match side {
+            Sides::WHITE => (self.wtime, self.winc),
+            _ => (self.btime, self.binc),
+        };
+
+        match time {
+            // Spend a tenth of the clock plus half the increment
+            Some(time) => {
+                let budget = time / 10 + inc.unwrap_or(0) / 2;
+                let budget = Duration::from_millis(budget.min(time));
+                (depth, budget.min(MAX_MOVE_TIME))
+            }
+            None => (depth, Duration::MAX),
+        }
+    }
+}
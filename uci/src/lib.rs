@@ -0,0 +1,4 @@
+pub mod engine;
+pub mod go;
+pub mod notation;
+pub mod position;
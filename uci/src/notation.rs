@@ -0,0 +1,84 @@
+use core::{
+    board::{
+        board::{parse_square, square_to_algebraic},
+        defs::{Piece, Pieces, Square},
+    },
+    movegen::moves::Move,
+    search::defs::{ScoreBound, SearchInfo, INF, MAX_DEPTH},
+};
+
+// Castles are written as the king's move, or as king takes rook in Chess960 mode
+pub fn move_to_alg(_move: &Move, chess960: bool) -> String {
+    let to = if chess960 { _move.to() } else { _move.destination() };
+    let mut alg = format!(
+        "{}{}",
+        square_to_algebraic(_move.from()),
+        square_to_algebraic(to)
+    );
+    if _move.is_promotion() {
+        match _move.promotion_piece() {
+            Pieces::BISHOP => alg += "b",
+            Pieces::KNIGHT => alg += "n",
+            Pieces::ROOK => alg += "r",
+            Pieces::QUEEN => alg += "q",
+            _ => panic!("Invalid promotion"),
+        }
+    }
+
+    alg
+}
+
+// Splits a long algebraic move like "e7e8q" into from, to and promotion piece
+pub fn parse_alg(alg: &str) -> Option<(Square, Square, Option<Piece>)> {
+    if !alg.is_ascii() || (alg.len() != 4 && alg.len() != 5) {
+        return None;
+    }
+
+    let from = parse_square(&alg[0..2])?;
+    let to = parse_square(&alg[2..4])?;
+    let promotion = match alg.as_bytes().get(4) {
+        None => None,
+        Some(b'n') => Some(Pieces::KNIGHT),
+        Some(b'b') => Some(Pieces::BISHOP),
+        Some(b'r') => Some(Pieces::ROOK),
+        Some(b'q') => Some(Pieces::QUEEN),
+        Some(_) => return None,
+    };
+
+    Some((from, to, promotion))
+}
+
+// Formats a score as "cp x" or "mate n", where n is in moves rather than plies
+pub fn format_score(score: i32) -> String {
+    let mate_distance = INF - score.abs();
+    if mate_distance < MAX_DEPTH as i32 * 2 {
+        let moves = (mate_distance + 1) / 2;
+        format!("mate {}", if score > 0 { moves } else { -moves })
+    } else {
+        format!("cp {}", score)
+    }
+}
+
+pub fn format_info(info: &SearchInfo, chess960: bool) -> String {
+    let millis = info.time.as_millis() as u64;
+    let nps = info.nodes * 1000 / millis.max(1);
+    let pv: Vec<String> = info.pv.iter().map(|mv| move_to_alg(mv, chess960)).collect();
+
+    let bound = match info.bound {
+        ScoreBound::Exact => "",
+        ScoreBound::Lower => " lowerbound",
+        ScoreBound::Upper => " upperbound",
+    };
+
+    format!(
+        "info depth {} score {}{} nodes {} nps {} hashfull {} time {} pv {}",
+        info.depth,
+        format_score(info.score),
+        bound,
+        info.nodes,
+        nps,
+        info.hashfull,
+        millis,
+        pv.join(" ")
+    )
+}
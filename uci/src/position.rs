@@ -0,0 +1,173 @@
+use core::{
+    board::{
+        board::Board,
+        defs::{InvalidFenError, START_POS},
+    },
+    movegen::{movegen::MoveGen, moves::Move},
+};
+use std::fmt;
+
+use crate::notation::parse_alg;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PositionError {
+    MissingPosition,
+    MissingFen,
+    UnexpectedToken(String),
+    InvalidFen(InvalidFenError),
+    MalformedMove(String),
+    IllegalMove(String),
+}
+
+impl fmt::Display for PositionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PositionError::MissingPosition => write!(f, "expected 'startpos' or 'fen'"),
+            PositionError::MissingFen => write!(f, "missing fen after 'position fen'"),
+            PositionError::UnexpectedToken(token) => write!(f, "unexpected token '{}'", token),
+            PositionError::InvalidFen(err) => write!(f, "invalid fen: {}", err),
+            PositionError::MalformedMove(mv) => write!(f, "malformed move '{}'", mv),
+            PositionError::IllegalMove(mv) => write!(f, "illegal move '{}'", mv),
+        }
+    }
+}
+
+impl From<InvalidFenError> for PositionError {
+    fn from(err: InvalidFenError) -> Self {
+        PositionError::InvalidFen(err)
+    }
+}
+
+// A parsed `position` command, kept around so the next one can be compared against it
+#[derive(Debug, Clone, PartialEq)]
+pub struct PositionCommand {
+    pub fen: String,
+    pub moves: Vec<String>,
+}
+
+impl PositionCommand {
+    // Parses the arguments following "position"
+    pub fn parse(args: &[&str]) -> Result<PositionCommand, PositionError> {
+        let mut index;
+        let fen = match args.first() {
+            Some(&"startpos") => {
+                index = 1;
+                START_POS.to_string()
+            }
+            Some(&"fen") => {
+                index = 1;
+                let mut fen_args = Vec::new();
+                while index < args.len() && args[index] != "moves" {
+                    fen_args.push(args[index]);
+                    index += 1;
+                }
+                if fen_args.is_empty() {
+                    return Err(PositionError::MissingFen);
+                }
+                fen_args.join(" ")
+            }
+            Some(token) => return Err(PositionError::UnexpectedToken(token.to_string())),
+            None => return Err(PositionError::MissingPosition),
+        };
+
+        let mut moves = Vec::new();
+        match args.get(index) {
+            None => (),
+            Some(&"moves") => {
+                for mv in &args[index + 1..] {
+                    if parse_alg(mv).is_none() {
+                        return Err(PositionError::MalformedMove(mv.to_string()));
+                    }
+                    moves.push(mv.to_string());
+                }
+            }
+            Some(token) => return Err(PositionError::UnexpectedToken(token.to_string())),
+        }
+
+        Ok(PositionCommand { fen, moves })
+    }
+
+    // If this command only appends moves to `previous`, returns the appended moves
+    pub fn extension_of(&self, previous: &PositionCommand) -> Option<&[String]> {
+        if self.fen == previous.fen && self.moves.starts_with(&previous.moves) {
+            Some(&self.moves[previous.moves.len()..])
+        } else {
+            None
+        }
+    }
+}
+
+// Finds the legal move described by a long algebraic string like "e2e4" or "e7e8q". Castles are
+// given as the king's move, or as king takes rook in Chess960 mode.
+pub fn parse_move(
+    board: &mut Board,
+    mg: &MoveGen,
+    alg: &str,
+    chess960: bool,
+) -> Result<Move, PositionError> {
+    let (from, to, promotion) =
+        parse_alg(alg).ok_or_else(|| PositionError::MalformedMove(alg.to_string()))?;
+
+    let moves = mg.gen_legal_moves(board);
+    let found = moves
+        .iter()
+        .find(|mv| {
+            let mv_promotion = if mv.is_promotion() {
+                Some(mv.promotion_piece())
+            } else {
+                None
+            };
+            let mv_to = if chess960 { mv.to() } else { mv.destination() };
+            mv.from() == from && mv_to == to && mv_promotion == promotion
+        })
+        .copied();
+
+    found.ok_or_else(|| PositionError::IllegalMove(alg.to_string()))
+}
+
+// Plays all moves, or none of them if any is malformed or illegal
+pub fn apply_moves(
+    board: &mut Board,
+    mg: &MoveGen,
+    moves: &[String],
+    chess960: bool,
+) -> Result<(), PositionError> {
+    let mut played: Vec<Move> = Vec::with_capacity(moves.len());
+
+    for alg in moves {
+        match parse_move(board, mg, alg, chess960) {
+            Ok(mv) => {
+                board.do_move(&mv);
+                played.push(mv);
+            }
+            Err(err) => {
+                for mv in played.iter().rev() {
+                    board.undo_move(mv);
+                }
+                return Err(err);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// Sets up `command` on `board`. When it only extends `previous` the moves are played on top of
+// the existing board, which keeps its history. On error the board is left untouched.
+pub fn set_position(
+    board: &mut Board,
+    mg: &MoveGen,
+    previous: Option<&PositionCommand>,
+    command: &PositionCommand,
+    chess960: bool,
+) -> Result<(), PositionError> {
+    if let Some(new_moves) = previous.and_then(|previous| command.extension_of(previous)) {
+        return apply_moves(board, mg, new_moves, chess960);
+    }
+
+    let mut new_board = Board::from_fen(&command.fen)?;
+    apply_moves(&mut new_board, mg, &command.moves, chess960)?;
+    *board = new_board;
+
+    Ok(())
+}
@@ -0,0 +1,177 @@
+use core::{
+    board::{
+        board::Board,
+        defs::{InvalidFenError, Pieces, START_POS},
+    },
+    movegen::movegen::MoveGen,
+};
+use uci::position::{parse_move, set_position, PositionCommand, PositionError};
+
+fn parse(command: &str) -> Result<PositionCommand, PositionError> {
+    let parts: Vec<&str> = command.split_whitespace().collect();
+    PositionCommand::parse(&parts[1..])
+}
+
+#[test]
+fn test_parse_startpos() {
+    let command = parse("position startpos moves e2e4 e7e5").unwrap();
+    assert_eq!(command.fen, START_POS);
+    assert_eq!(command.moves, vec!["e2e4", "e7e5"]);
+
+    let command = parse("position startpos").unwrap();
+    assert!(command.moves.is_empty());
+}
+
+#[test]
+fn test_parse_fen() {
+    let fen = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+    let command = parse(&format!("position fen {} moves e1g1", fen)).unwrap();
+    assert_eq!(command.fen, fen);
+    assert_eq!(command.moves, vec!["e1g1"]);
+}
+
+#[test]
+fn test_parse_errors() {
+    assert_eq!(parse("position"), Err(PositionError::MissingPosition));
+    assert_eq!(parse("position fen"), Err(PositionError::MissingFen));
+    assert_eq!(
+        parse("position fen moves e2e4"),
+        Err(PositionError::MissingFen)
+    );
+    assert_eq!(
+        parse("position somewhere"),
+        Err(PositionError::UnexpectedToken("somewhere".to_string()))
+    );
+    assert_eq!(
+        parse("position startpos e2e4"),
+        Err(PositionError::UnexpectedToken("e2e4".to_string()))
+    );
+    assert_eq!(
+        parse("position startpos moves e2e4 e7e9"),
+        Err(PositionError::MalformedMove("e7e9".to_string()))
+    );
+    assert_eq!(
+        parse("position startpos moves e7e8k"),
+        Err(PositionError::MalformedMove("e7e8k".to_string()))
+    );
+}
+
+#[test]
+fn test_invalid_fen_keeps_position() {
+    let mg = MoveGen;
+    let mut board = Board::from_fen(START_POS).unwrap();
+    let previous = parse("position startpos moves e2e4").unwrap();
+    set_position(&mut board, &mg, None, &previous, false).unwrap();
+    let hash = board.zobrist_hash();
+
+    for (fen, err) in [
+        ("8/8/8/8/8/8/8 w - -", InvalidFenError::InvalidRankCount),
+        (
+            "rnbqkbnr/pppppppp/9/8/8/8/PPPPPPPP/RNBQKBNR w KQkq -",
+            InvalidFenError::InvalidRankLength,
+        ),
+        (
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNX w KQkq -",
+            InvalidFenError::InvalidPiece,
+        ),
+        (
+            "rnbq1bnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQ -",
+            InvalidFenError::InvalidKingCount,
+        ),
+        (
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR x KQkq -",
+            InvalidFenError::InvalidActiveColor,
+        ),
+        (
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq z9",
+            InvalidFenError::InvalidEnPassantSquare,
+        ),
+    ] {
+        let command = parse(&format!("position fen {}", fen)).unwrap();
+        assert_eq!(
+            set_position(&mut board, &mg, Some(&previous), &command, false),
+            Err(PositionError::InvalidFen(err))
+        );
+        assert_eq!(board.zobrist_hash(), hash);
+    }
+}
+
+#[test]
+fn test_illegal_move_keeps_position() {
+    let mg = MoveGen;
+    let mut board = Board::from_fen(START_POS).unwrap();
+    let previous = parse("position startpos moves e2e4").unwrap();
+    set_position(&mut board, &mg, None, &previous, false).unwrap();
+    let hash = board.zobrist_hash();
+
+    // Extension of the previous command, the first new move gets played then rolled back
+    let command = parse("position startpos moves e2e4 e7e5 e1e3").unwrap();
+    assert_eq!(
+        set_position(&mut board, &mg, Some(&previous), &command, false),
+        Err(PositionError::IllegalMove("e1e3".to_string()))
+    );
+    assert_eq!(board.zobrist_hash(), hash);
+    assert_eq!(board.history.count_hash(hash), 1);
+
+    // Fresh position
+    let command = parse("position startpos moves d2d4 d2d4").unwrap();
+    assert_eq!(
+        set_position(&mut board, &mg, Some(&previous), &command, false),
+        Err(PositionError::IllegalMove("d2d4".to_string()))
+    );
+    assert_eq!(board.zobrist_hash(), hash);
+}
+
+#[test]
+fn test_promotion_requires_piece() {
+    let mg = MoveGen;
+    let mut board = Board::from_fen("8/4P3/8/8/8/k7/8/K7 w - - 0 1").unwrap();
+
+    assert_eq!(
+        parse_move(&mut board, &mg, "e7e8", false),
+        Err(PositionError::IllegalMove("e7e8".to_string()))
+    );
+
+    for (alg, piece) in [
+        ("e7e8q", Pieces::QUEEN),
+        ("e7e8r", Pieces::ROOK),
+        ("e7e8b", Pieces::BISHOP),
+        ("e7e8n", Pieces::KNIGHT),
+    ] {
+        let mv = parse_move(&mut board, &mg, alg, false).unwrap();
+        assert!(mv.is_promotion());
+        assert_eq!(mv.promotion_piece(), piece);
+    }
+}
+
+#[test]
+fn test_extension_reuses_board() {
+    let mg = MoveGen;
+    let mut board = Board::from_fen(START_POS).unwrap();
+
+    let first = parse("position startpos moves g1f3 g8f6").unwrap();
+    let second = parse("position startpos moves g1f3 g8f6 f3g1 f6g8").unwrap();
+    let unrelated = parse("position startpos moves e2e4").unwrap();
+
+    assert_eq!(second.extension_of(&first), Some(&second.moves[2..]));
+    assert_eq!(unrelated.extension_of(&first), None);
+    assert_eq!(first.extension_of(&second), None);
+
+    set_position(&mut board, &mg, None, &first, false).unwrap();
+    set_position(&mut board, &mg, Some(&first), &second, false).unwrap();
+
+    // Back to the start position, which was seen twice along the kept history
+    let start = Board::from_fen(START_POS).unwrap();
+    assert_eq!(board.zobrist_hash(), start.zobrist_hash());
+    assert_eq!(board.history.count_hash(board.zobrist_hash()), 1);
+}
+
+#[test]
+fn test_en_passant_from_fen() {
+    let mg = MoveGen;
+    let mut board =
+        Board::from_fen("rnbqkbnr/ppp1pppp/8/8/3pP3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 3").unwrap();
+
+    let mv = parse_move(&mut board, &mg, "d4e3", false).unwrap();
+    assert!(mv.is_enpassant());
+}
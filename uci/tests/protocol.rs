@@ -0,0 +1,462 @@
+use core::{
+    board::{board::Board, defs::START_POS},
+    movegen::movegen::MoveGen,
+    search::defs::{ScoreBound, SearchInfo},
+};
+use std::time::{Duration, Instant};
+use uci::{
+    engine::UciEngine,
+    notation::format_info,
+    position::{parse_move, set_position, PositionCommand},
+};
+
+fn run_session(script: &str) -> Vec<String> {
+    let mut engine = UciEngine::new(Vec::new());
+    engine.run(script.as_bytes());
+
+    String::from_utf8(engine.into_output())
+        .unwrap()
+        .lines()
+        .map(String::from)
+        .collect()
+}
+
+fn bestmoves(output: &[String]) -> Vec<&str> {
+    output
+        .iter()
+        .filter_map(|line| line.strip_prefix("bestmove "))
+        .collect()
+}
+
+// Info lines without the timing dependent fields
+fn search_trace(output: &[String]) -> Vec<String> {
+    output
+        .iter()
+        .filter(|line| line.starts_with("info depth"))
+        .map(|line| {
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            let nps = parts.iter().position(|&p| p == "nps").unwrap();
+            let pv = parts.iter().position(|&p| p == "pv").unwrap();
+            [&parts[..nps], &parts[pv..]].concat().join(" ")
+        })
+        .collect()
+}
+
+// Asserts that `mv` is legal after the given `position` command
+fn assert_legal(position: &str, mv: &str) {
+    let mg = MoveGen;
+    let mut board = Board::from_fen(START_POS).unwrap();
+    let parts: Vec<&str> = position.split_whitespace().collect();
+    let command = PositionCommand::parse(&parts[1..]).unwrap();
+    set_position(&mut board, &mg, None, &command, false).unwrap();
+
+    assert!(
+        parse_move(&mut board, &mg, mv, false).is_ok(),
+        "bestmove {} is not legal after '{}'",
+        mv,
+        position
+    );
+}
+
+#[test]
+fn test_uci_handshake() {
+    let output = run_session("uci\n");
+
+    assert!(output.iter().any(|line| line.starts_with("id name ")));
+    assert!(output.iter().any(|line| line.starts_with("id author ")));
+    assert!(output
+        .iter()
+        .any(|line| line.starts_with("option name Hash type spin")));
+    assert!(output
+        .iter()
+        .any(|line| line == "option name Clear Hash type button"));
+    assert!(output
+        .iter()
+        .any(|line| line.starts_with("option name Threads type spin default 1 min 1")));
+    assert!(output
+        .iter()
+        .any(|line| line == "option name Keep Hash type check default true"));
+    assert!(output
+        .iter()
+        .any(|line| line == "option name UCI_Chess960 type check default false"));
+    assert!(output
+        .iter()
+        .any(|line| line == "option name SingularExtension type check default true"));
+    assert!(output.iter().any(|line| line
+        == "option name InternalIteration type combo default Reductions var Off var Reductions \
+            var Deepening"));
+    // Tunable parameters stay hidden unless asked for
+    assert!(!output.iter().any(|line| line.starts_with("option name LmrBase")));
+    assert_eq!(output.last().unwrap(), "uciok");
+}
+
+#[test]
+fn test_tunables() {
+    let mut engine = UciEngine::new(Vec::new());
+    engine.show_tunables();
+    engine.run("uci\n".as_bytes());
+    let output = String::from_utf8(engine.into_output()).unwrap();
+    assert!(output
+        .lines()
+        .any(|line| line == "option name LmrBase type spin default 278 min 0 max 500"));
+
+    let output = run_session("spsa\n");
+    assert!(output.contains(&"RfpMargin, int, 150, 50, 300, 10, 0.002".to_string()));
+    assert!(output.contains(&"QueenValue, int, 900, 750, 1100, 25, 0.002".to_string()));
+
+    let output = run_session("setoption name RfpMargin value 200\nspsa\n");
+    assert!(output.contains(&"RfpMargin, int, 200, 50, 300, 10, 0.002".to_string()));
+
+    let output = run_session("setoption name RfpMargin value 1000\n");
+    assert_eq!(output, vec!["info string invalid RfpMargin value '1000'"]);
+
+    let output = run_session("setoption name LmrFactor value abc\n");
+    assert_eq!(output, vec!["info string invalid LmrFactor value 'abc'"]);
+
+    // Modes are picked by name and never handed to SPSA
+    let output = run_session("setoption name InternalIteration value deepening\nspsa\n");
+    assert!(!output.iter().any(|line| line.starts_with("InternalIteration")));
+    assert!(!output.iter().any(|line| line.starts_with("info string")));
+    let output = run_session("setoption name InternalIteration value 2\n");
+    assert_eq!(output, vec!["info string invalid InternalIteration value '2'"]);
+}
+
+#[test]
+fn test_isready() {
+    let output = run_session("isready\nisready\n");
+    assert_eq!(output, vec!["readyok", "readyok"]);
+}
+
+#[test]
+fn test_go_depth() {
+    let position = "position startpos moves e2e4 e7e5 g1f3";
+    let output = run_session(&format!("{}\ngo depth 3\n", position));
+
+    for depth in 1..=3 {
+        let prefix = format!("info depth {} score ", depth);
+        let info = output
+            .iter()
+            .find(|line| line.starts_with(&prefix))
+            .unwrap_or_else(|| panic!("No info line for depth {}", depth));
+        assert!(info.contains(" nodes "));
+        assert!(info.contains(" pv "));
+    }
+    assert!(!output.iter().any(|line| line.starts_with("info depth 4 ")));
+
+    let bestmoves = bestmoves(&output);
+    assert_eq!(bestmoves.len(), 1);
+    assert_legal(position, bestmoves[0]);
+}
+
+#[test]
+fn test_go_movetime() {
+    let position =
+        "position fen r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+    let start = Instant::now();
+    let output = run_session(&format!("{}\ngo movetime 300\n", position));
+
+    assert!(start.elapsed() < Duration::from_secs(10));
+    let bestmoves = bestmoves(&output);
+    assert_eq!(bestmoves.len(), 1);
+    assert_legal(position, bestmoves[0]);
+}
+
+#[test]
+fn test_go_infinite_stop() {
+    let position = "position startpos moves d2d4";
+    let output = run_session(&format!("{}\ngo infinite\nstop\n", position));
+
+    let bestmoves = bestmoves(&output);
+    assert_eq!(bestmoves.len(), 1);
+    assert_legal(position, bestmoves[0]);
+}
+
+#[test]
+fn test_isready_during_search() {
+    let output = run_session("position startpos\ngo depth 3\nisready\n");
+
+    assert!(output.iter().any(|line| line == "readyok"));
+    assert_eq!(bestmoves(&output).len(), 1);
+}
+
+#[test]
+fn test_consecutive_searches() {
+    let script = "position startpos\n\
+                  go depth 2\n\
+                  position startpos moves e2e4\n\
+                  go depth 2\n\
+                  position startpos moves e2e4 c7c5\n\
+                  go depth 2\n";
+    let output = run_session(script);
+
+    let bestmoves = bestmoves(&output);
+    assert_eq!(bestmoves.len(), 3);
+    assert_legal("position startpos", bestmoves[0]);
+    assert_legal("position startpos moves e2e4", bestmoves[1]);
+    assert_legal("position startpos moves e2e4 c7c5", bestmoves[2]);
+}
+
+#[test]
+fn test_mate_in_one() {
+    let output = run_session("position fen 6k1/5ppp/8/8/8/8/5PPP/R5K1 w - - 0 1\ngo depth 3\n");
+
+    assert_eq!(bestmoves(&output), vec!["a1a8"]);
+    assert!(output
+        .iter()
+        .any(|line| line.starts_with("info depth 3 score mate 1 ")));
+}
+
+#[test]
+fn test_no_legal_moves() {
+    // Black is checkmated
+    let output = run_session("position fen R5k1/5ppp/8/8/8/8/5PPP/6K1 b - - 1 1\ngo depth 2\n");
+    assert_eq!(bestmoves(&output), vec!["0000"]);
+}
+
+#[test]
+fn test_setoption() {
+    let output = run_session("setoption name Hash value 16\nisready\n");
+    assert_eq!(output, vec!["readyok"]);
+
+    let output = run_session("setoption name Hash value 0\n");
+    assert_eq!(output, vec!["info string invalid Hash value '0'"]);
+
+    let output = run_session("setoption name Threads value 0\n");
+    assert_eq!(output, vec!["info string invalid Threads value '0'"]);
+
+    let output = run_session("setoption name CheckExtension value false\nisready\n");
+    assert_eq!(output, vec!["readyok"]);
+
+    let output = run_session("setoption name PawnPushExtension value 1\n");
+    assert_eq!(output, vec!["info string invalid PawnPushExtension value '1'"]);
+
+    let output = run_session("setoption name Contempt Factor value 20\n");
+    assert_eq!(output, vec!["info string unknown option 'Contempt Factor'"]);
+}
+
+#[test]
+fn test_ucinewgame() {
+    let script = "position startpos moves e2e4 e7e5\n\
+                  ucinewgame\n\
+                  go depth 2\n";
+    let output = run_session(script);
+
+    let bestmoves = bestmoves(&output);
+    assert_eq!(bestmoves.len(), 1);
+    assert_legal("position startpos", bestmoves[0]);
+}
+
+#[test]
+fn test_invalid_position_is_reported() {
+    let script = "position startpos moves e2e4\n\
+                  position startpos moves e2e4 e7e5 e1e8\n\
+                  go depth 2\n";
+    let output = run_session(script);
+
+    assert!(output
+        .iter()
+        .any(|line| line == "info string illegal move 'e1e8'"));
+    // The search runs from the last valid position, with black to move
+    assert_legal("position startpos moves e2e4", bestmoves(&output)[0]);
+}
+
+#[test]
+fn test_quit() {
+    let output = run_session("isready\nquit\nisready\n");
+    assert_eq!(output, vec!["readyok"]);
+}
+
+#[test]
+fn test_bestmove_legality() {
+    let positions = [
+        "position fen r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        "position fen 8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+        "position fen 8/4P1k1/8/8/8/8/1p4K1/8 w - - 0 1",
+        "position fen 8/4P1k1/8/8/8/8/1p4K1/8 b - - 0 1",
+        "position fen rnbqkbnr/ppp1pppp/8/8/3pP3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 3",
+        "position startpos moves e2e4 e7e5 g1f3 b8c6 f1c4 g8f6 e1g1",
+    ];
+
+    for position in positions {
+        let output = run_session(&format!("{}\ngo depth 2\n", position));
+        let bestmoves = bestmoves(&output);
+        assert_eq!(bestmoves.len(), 1);
+        assert_legal(position, bestmoves[0]);
+    }
+}
+
+#[test]
+fn test_threads() {
+    let position = "position fen r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+    let output = run_session(&format!(
+        "setoption name Threads value 4\n{}\ngo movetime 200\nisready\n{}\ngo depth 3\n",
+        position, position
+    ));
+
+    let bestmoves = bestmoves(&output);
+    assert_eq!(bestmoves.len(), 2);
+    for mv in bestmoves {
+        assert_legal(position, mv);
+    }
+}
+
+#[test]
+fn test_ucinewgame_is_reproducible() {
+    let game = "position startpos moves e2e4 e7e5\ngo depth 3\n";
+    let first = run_session(game);
+    let second = run_session(&format!("{}ucinewgame\n{}", game, game));
+
+    let trace = search_trace(&first);
+    assert_eq!(search_trace(&second), [trace.clone(), trace].concat());
+}
+
+#[test]
+fn test_clear_hash() {
+    let game = "position startpos moves d2d4\ngo depth 3\n";
+    let first = run_session(game);
+    let second = run_session(&format!("{}setoption name Clear Hash\n{}", game, game));
+
+    let trace = search_trace(&first);
+    assert_eq!(search_trace(&second), [trace.clone(), trace].concat());
+}
+
+#[test]
+fn test_keep_hash() {
+    let game = "position startpos moves d2d4\ngo depth 3\n";
+    let first = run_session(game);
+    let trace = search_trace(&first);
+
+    // The second search reuses the table from the first one, yet still searches the root
+    // moves and comes to the same answer
+    let kept = run_session(&format!("{}{}", game, game));
+    assert_eq!(bestmoves(&kept), [bestmoves(&first), bestmoves(&first)].concat());
+    let last_info = kept.iter().rev().find(|line| line.starts_with("info depth 3")).unwrap();
+    let parts: Vec<&str> = last_info.split_whitespace().collect();
+    let nodes = parts.iter().position(|&p| p == "nodes").unwrap();
+    assert!(parts[nodes + 1].parse::<u64>().unwrap() > 20, "{}", last_info);
+
+    let cleared = run_session(&format!(
+        "setoption name Keep Hash value false\n{}{}",
+        game, game
+    ));
+    assert_eq!(search_trace(&cleared), [trace.clone(), trace].concat());
+}
+
+#[test]
+fn test_debug_display() {
+    let output = run_session("position startpos moves e2e4\nd\n");
+
+    assert!(output
+        .iter()
+        .any(|line| line == " | P | P | P | P |   | P | P | P | 2"));
+    assert!(output
+        .iter()
+        .any(|line| line == "Fen: rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1"));
+    assert!(output.iter().any(|line| line.starts_with("Key: ")));
+}
+
+#[test]
+fn test_debug_eval() {
+    let output = run_session("position fen 4k3/8/8/8/8/8/8/3QK3 b - - 0 1\neval\n");
+
+    assert!(output
+        .iter()
+        .any(|line| line.starts_with("Material      |   900")));
+    assert!(output.iter().any(|line| line == "Scale: 128/128"));
+    let final_line = output.last().unwrap();
+    assert!(final_line.starts_with("Final evaluation: -"));
+}
+
+#[test]
+fn test_debug_perft() {
+    let output = run_session("position startpos\nperft 2\n");
+
+    assert_eq!(
+        output
+            .iter()
+            .filter(|line| line.ends_with(": 20") && !line.starts_with("Nodes"))
+            .count(),
+        20
+    );
+    assert!(output.iter().any(|line| line == "Nodes searched: 400"));
+
+    let output = run_session("perft x\n");
+    assert_eq!(output, vec!["info string usage: perft <depth>"]);
+}
+
+#[test]
+fn test_debug_flip() {
+    let output = run_session("position startpos moves e2e4\nflip\nd\ngo depth 1\n");
+
+    assert!(output
+        .iter()
+        .any(|line| line == "Fen: rnbqkbnr/pppp1ppp/8/4p3/8/8/PPPPPPPP/RNBQKBNR w KQkq e6 0 1"));
+    // White moves in the flipped position
+    assert_legal(
+        "position fen rnbqkbnr/pppp1ppp/8/4p3/8/8/PPPPPPPP/RNBQKBNR w KQkq e6 0 1",
+        bestmoves(&output)[0],
+    );
+}
+
+#[test]
+fn test_debug_during_search() {
+    let output =
+        run_session("position startpos\ngo infinite\neval\nspsa\nperft 1\nflip\nd\nstop\n");
+
+    for command in ["eval", "spsa", "perft", "flip"] {
+        let line = format!("info string {} unavailable while searching", command);
+        assert!(output.contains(&line), "{}", command);
+    }
+    // The board wasn't flipped
+    assert!(output.iter().any(|line| line.starts_with("Fen: rnbqkbnr/pppppppp/")));
+    assert_eq!(bestmoves(&output).len(), 1);
+}
+
+#[test]
+fn test_chess960_castling() {
+    let fen = "position fen 1r2k1r1/8/8/8/8/8/8/RR2K3 w Bkq - 0 1";
+
+    // Standard notation gives the king's destination
+    let output = run_session(&format!("{}\nperft 1\n", fen));
+    assert!(output.iter().any(|line| line == "e1c1: 1"));
+    let output = run_session(&format!("{} moves e1c1 e8g8\nd\n", fen));
+    assert!(output
+        .iter()
+        .any(|line| line == "Fen: 1r3rk1/8/8/8/8/8/8/R1KR4 w - - 2 2"));
+
+    // Chess960 notation is king takes rook
+    let script = format!(
+        "setoption name UCI_Chess960 value true\n{}\nperft 1\n{} moves e1b1 e8g8\nd\n",
+        fen, fen
+    );
+    let output = run_session(&script);
+    assert!(output.iter().any(|line| line == "e1b1: 1"));
+    assert!(!output.iter().any(|line| line == "e1c1: 1"));
+    assert!(output
+        .iter()
+        .any(|line| line == "Fen: 1r3rk1/8/8/8/8/8/8/R1KR4 w - - 2 2"));
+
+    let output = run_session("setoption name UCI_Chess960 value maybe\n");
+    assert_eq!(output, vec!["info string invalid UCI_Chess960 value 'maybe'"]);
+}
+
+#[test]
+fn test_info_bounds() {
+    let mut info = SearchInfo {
+        depth: 5,
+        score: 30,
+        bound: ScoreBound::Lower,
+        nodes: 1000,
+        hashfull: 12,
+        time: Duration::from_millis(10),
+        pv: Vec::new(),
+    };
+    assert!(format_info(&info, false).starts_with("info depth 5 score cp 30 lowerbound nodes "));
+
+    info.bound = ScoreBound::Upper;
+    assert!(format_info(&info, false).starts_with("info depth 5 score cp 30 upperbound nodes "));
+
+    info.bound = ScoreBound::Exact;
+    assert!(format_info(&info, false).starts_with("info depth 5 score cp 30 nodes "));
+    assert!(format_info(&info, false).contains(" hashfull 12 "));
+}